@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use crate::grid::Coord;
+
+const TRAIL_DURATION: Duration = Duration::from_millis(150);
+const FLASH_DURATION: Duration = Duration::from_millis(300);
+
+/// Purely cosmetic effects layered over the grid. The grid itself is always
+/// up to date; these only change how it is drawn for a few frames.
+#[derive(Default)]
+pub struct Animations {
+    pub tweens: Vec<Tween>,
+    trails: Vec<(Coord, Instant)>,
+    flashes: Vec<(Coord, Instant)>,
+}
+
+/// Something sliding from one cell to the next. It already lives at `to`.
+pub struct Tween {
+    pub from: Coord,
+    pub to: Coord,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Tween {
+    /// How far along the slide is, from 0 to 1.
+    pub fn progress(&self, now: Instant) -> f64 {
+        (now.duration_since(self.started).as_secs_f64() / self.duration.as_secs_f64()).min(1.0)
+    }
+}
+
+impl Animations {
+    pub fn tween(&mut self, from: Coord, to: Coord, duration: Duration) {
+        // A new move cuts short whatever slide ended where it starts.
+        self.tweens.retain(|tween| tween.to != from);
+        self.tweens.push(Tween {
+            from,
+            to,
+            started: Instant::now(),
+            duration,
+        });
+    }
+
+    pub fn trail(&mut self, at: Coord) {
+        self.trails.push((at, Instant::now()));
+    }
+
+    pub fn flash(&mut self, at: Coord) {
+        self.flashes.push((at, Instant::now()));
+    }
+
+    pub fn tween_to(&self, at: Coord) -> Option<&Tween> {
+        self.tweens.iter().find(|tween| tween.to == at)
+    }
+
+    pub fn has_trail(&self, at: Coord) -> bool {
+        self.trails.iter().any(|&(spot, _)| spot == at)
+    }
+
+    pub fn is_flashing(&self, at: Coord) -> bool {
+        self.flashes.iter().any(|&(spot, _)| spot == at)
+    }
+
+    pub fn prune(&mut self, now: Instant) {
+        self.tweens.retain(|tween| tween.progress(now) < 1.0);
+        self.trails
+            .retain(|&(_, started)| now.duration_since(started) < TRAIL_DURATION);
+        self.flashes
+            .retain(|&(_, started)| now.duration_since(started) < FLASH_DURATION);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
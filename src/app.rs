@@ -1,19 +1,33 @@
+use color_eyre::{
+    eyre::{bail, Report},
+    Result,
+};
 use ratatui::{
-    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    crossterm::event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
+    layout::Rect,
     DefaultTerminal,
 };
 use std::{
     borrow::BorrowMut,
-    fmt, io,
-    ops::{
-        Add,
-        ControlFlow::{self, Break, Continue},
-        Index, IndexMut, Mul, Neg, RemAssign, Sub,
-    },
-    time::{Duration, Instant, SystemTime},
+    collections::{HashSet, VecDeque},
+    fmt, mem,
+    ops::ControlFlow::{self, Break, Continue},
+    time::{Duration, Instant},
 };
 
+use crate::anim::Animations;
+use crate::config::Config;
 use crate::enums::*;
+use crate::grid::{Coord, Grid};
+use crate::levels;
+use crate::path;
+use crate::position::{self, box_key, player_key, terrain_key};
+use crate::records::{Record, Records};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 
 impl fmt::Display for MenuItem {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -24,7 +38,9 @@ impl fmt::Display for MenuItem {
 impl MenuItem {
     fn next(&self) -> Self {
         match self {
-            Self::Play => Self::Options,
+            Self::Play => Self::Levels,
+            Self::Levels => Self::Paste,
+            Self::Paste => Self::Options,
             Self::Options => Self::Quit,
             Self::Quit => Self::Play,
         }
@@ -34,71 +50,297 @@ impl MenuItem {
         match self {
             Self::Play => Self::Quit,
             Self::Quit => Self::Options,
-            Self::Options => Self::Play,
+            Self::Options => Self::Paste,
+            Self::Paste => Self::Levels,
+            Self::Levels => Self::Play,
+        }
+    }
+}
+
+impl OptionItem {
+    fn next(&self) -> Self {
+        match self {
+            Self::Animations => Self::BulletTrails,
+            Self::BulletTrails => Self::LockFlash,
+            Self::LockFlash => Self::Glyphs,
+            Self::Glyphs => Self::RepeatHint,
+            Self::RepeatHint => Self::Mode,
+            Self::Mode => Self::Back,
+            Self::Back => Self::Animations,
+        }
+    }
+
+    fn prev(&self) -> Self {
+        match self {
+            Self::Animations => Self::Back,
+            Self::Back => Self::Mode,
+            Self::Mode => Self::RepeatHint,
+            Self::RepeatHint => Self::Glyphs,
+            Self::Glyphs => Self::LockFlash,
+            Self::LockFlash => Self::BulletTrails,
+            Self::BulletTrails => Self::Animations,
+        }
+    }
+}
+
+impl CompleteItem {
+    fn next(&self) -> Self {
+        match self {
+            Self::Next => Self::Replay,
+            Self::Replay => Self::Solution,
+            Self::Solution => Self::Menu,
+            Self::Menu => Self::Next,
+        }
+    }
+
+    fn prev(&self) -> Self {
+        match self {
+            Self::Next => Self::Menu,
+            Self::Menu => Self::Solution,
+            Self::Solution => Self::Replay,
+            Self::Replay => Self::Next,
+        }
+    }
+}
+
+impl GlyphSet {
+    fn next(&self) -> Self {
+        match self {
+            Self::Blocks => Self::Ascii,
+            Self::Ascii => Self::Blocks,
+        }
+    }
+}
+
+impl Mode {
+    fn next(&self) -> Self {
+        match self {
+            Self::Forward => Self::Reverse,
+            Self::Reverse => Self::Forward,
         }
     }
 }
 
 pub struct App {
     pub current_screen: CurrentScreen,
+    pub config: Config,
+    pub animations: Animations,
     pub next_level: usize,
     pub timing_buffer: [Duration; 30],
     pub timing_index: u8,
+    pub pending_moves: VecDeque<Direction>,
+    pub selected_box: Option<Coord>,
+    /// Where `selected_box` is being steered to from the keyboard.
+    pub ghost: Option<Ghost>,
+    /// The square picked to walk to, while picking one from the keyboard.
+    pub cursor: Option<Coord>,
+    pub show_help: bool,
+    /// The current level as an RLE string, while it's being shown to share.
+    pub share: Option<String>,
+    pub paused: bool,
+    pub records: Records,
+    /// The levels being played through, in order.
+    pub levels: Vec<Level>,
+    /// Names the source of `levels`, so records from different files don't mix.
+    pub collection: String,
 }
 
+/// A level being played, or ready to be. Saves as JSON through `Saved`, which
+/// `levels::parse_json` documents.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "Saved")]
 pub struct Level {
-    pub player_location: Coord,
-    pub level_state: Grid<Cell>,
+    #[serde(skip)]
+    pub index: usize,
+    pub meta: Metadata,
+    /// Everyone on the board, in reading order. Multiban levels have several.
+    pub players: Vec<Avatar>,
+    /// Which of `players` the keys move.
+    pub active: usize,
+    /// The floor of every square: walls, targets and special floor.
+    pub terrain: Grid<Terrain>,
+    /// Whatever stands on each square: players, boxes, turrets and bullets.
+    pub occupants: Grid<Occupant>,
     pub move_counter: usize,
+    pub push_counter: usize,
+    #[serde(skip)]
     pub remaining_boxes: usize,
+    /// Every move so far in LURD notation, with pushes in upper case.
+    pub history: String,
+    pub time: Duration,
+    /// Zobrist hash of where the player and boxes are, kept up to date by
+    /// `move_player`.
+    #[serde(skip)]
+    pub hash: u64,
+    /// Hashes of every position reached so far.
+    #[serde(skip)]
+    pub seen: HashSet<u64>,
+    /// Whether the last move led back to a position already seen.
+    #[serde(skip)]
+    pub repeated: bool,
+    pub mode: Mode,
+    /// In reverse mode, whether stepping away from a box drags it along.
+    #[serde(skip)]
+    pub pulling: bool,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-pub struct Coord(u16, u16);
+/// A level as read from JSON. Only the board is needed; the rest is how far
+/// play had got, for saves.
+#[derive(Deserialize)]
+struct Saved {
+    #[serde(default)]
+    meta: Metadata,
+    terrain: Grid<Terrain>,
+    occupants: Grid<Occupant>,
+    #[serde(default)]
+    mode: Mode,
+    #[serde(default)]
+    players: Vec<Avatar>,
+    #[serde(default)]
+    active: usize,
+    #[serde(default)]
+    move_counter: usize,
+    #[serde(default)]
+    push_counter: usize,
+    #[serde(default)]
+    history: String,
+    #[serde(default)]
+    time: Duration,
+}
 
-#[derive(Clone, Copy)]
-pub struct Offset(i16, i16);
+/// What a level file says about a level besides its board.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub par_moves: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub par_pushes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    /// The mechanics the level is built around. Filled in from the board
+    /// when the file doesn't list them.
+    pub mechanics: Vec<Mechanic>,
+    /// Ticks a turret waits between shots.
+    pub fire_rate: u8,
+}
 
-#[derive(Debug, Clone)]
-pub struct Grid<T>(pub Vec<Vec<T>>);
+impl Default for Metadata {
+    fn default() -> Self {
+        Self {
+            title: None,
+            author: None,
+            difficulty: None,
+            par_moves: None,
+            par_pushes: None,
+            hint: None,
+            mechanics: vec![],
+            fire_rate: 2,
+        }
+    }
+}
 
-impl<T> Index<Coord> for Grid<T> {
-    type Output = T;
+/// One of the players on the board, with its own tally of moves and its own
+/// undo history. The undo history isn't saved.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Avatar {
+    pub location: Coord,
+    /// Cleared once a turret has finished this player off.
+    pub alive: bool,
+    pub moves: usize,
+    pub pushes: usize,
+    /// Keys picked up and not yet used on a door.
+    #[serde(default)]
+    pub keys: Vec<u8>,
+    #[serde(skip)]
+    undo: Vec<Step>,
+}
 
-    fn index(&self, index: Coord) -> &Self::Output {
-        &self.0[index.1 as usize][index.0 as usize]
-    }
+/// A box being pushed from the keyboard: where it would end up, and whether
+/// trying to push it there found no way.
+pub struct Ghost {
+    pub at: Coord,
+    pub stuck: bool,
 }
 
-impl<T> IndexMut<Coord> for Grid<T> {
-    fn index_mut(&mut self, index: Coord) -> &mut Self::Output {
-        &mut self.0[index.1 as usize][index.0 as usize]
-    }
+/// A move that can be taken back.
+#[derive(Clone)]
+struct Step {
+    from: Coord,
+    moved_box: Option<(Coord, Coord)>,
+    /// The hole the box fell into, if it did.
+    filled: Option<Coord>,
+    /// Keys picked up and doors opened, with what the floor was before.
+    changed: Vec<(Coord, Terrain)>,
+    /// The keys the player held before.
+    keys: Vec<u8>,
+    /// Where the move was written down in `Level::history`.
+    index: usize,
+    /// Made as part of the same action as the step before, so undone along
+    /// with it.
+    joined: bool,
+}
+
+/// What a single step did, so the interface can animate it.
+pub struct Moved {
+    pub from: Coord,
+    /// Where a pushed or pulled box came from and landed, and whether it just
+    /// locked onto a target.
+    pub moved_box: Option<(Coord, Coord, bool)>,
+}
+
+/// How a finished level went, for the completion screen.
+pub struct Summary {
+    pub level: usize,
+    pub attempt: Record,
+    pub hp: u8,
+    /// The personal best going in, if the level had been finished before.
+    pub best: Option<Record>,
+    pub par_moves: Option<usize>,
+    pub par_pushes: Option<usize>,
+    pub solution: String,
+    pub show_solution: bool,
 }
 
 impl App {
-    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
         let target_fps = 60.0;
         let mut now = Instant::now();
         let mut delta = now.elapsed();
         let mut processed_time = delta;
         loop {
             terminal.draw(|frame| self.draw(frame))?;
-            match event::poll(Duration::from_secs_f64(1f64 / target_fps).saturating_sub(delta)) {
-                Ok(true) => {
-                    let Ok(Event::Key(key)) = event::read() else {
-                        continue;
-                    };
-                    match self.process_input(key) {
+            if let Ok(true) =
+                event::poll(Duration::from_secs_f64(1f64 / target_fps).saturating_sub(delta))
+            {
+                match event::read() {
+                    Ok(Event::Key(key)) => match self.process_input(key) {
                         Continue(()) => {}
-                        Break(b) => return Ok(()),
-                    };
+                        Break(_) => return Ok(()),
+                    },
+                    Ok(Event::Paste(text)) => self.paste(&text),
+                    Ok(Event::FocusLost) => self.pause(),
+                    Ok(Event::Mouse(mouse)) if !self.paused => {
+                        let size = terminal.size()?;
+                        self.process_mouse(mouse, Rect::new(0, 0, size.width, size.height));
+                    }
+                    _ => continue,
                 }
-                _ => {}
-            };
+            }
 
             delta = now.elapsed();
-            processed_time += delta;
+            if !self.is_frozen() {
+                processed_time += delta;
+                if let CurrentScreen::Game(level) = &mut self.current_screen {
+                    level.time += delta;
+                }
+            }
             now = Instant::now();
 
             self.timing_buffer[self.timing_index as usize] = delta;
@@ -111,90 +353,189 @@ impl App {
             }
             self.update();
         }
+    }
+
+    /// Stops the clock, if there is a game running for it to matter.
+    fn pause(&mut self) {
+        if let CurrentScreen::Game(_) = self.current_screen {
+            self.paused = true;
+        }
+    }
+
+    /// Whether the game clock is stopped, either explicitly or because the
+    /// player is reading the help or a level to share.
+    fn is_frozen(&self) -> bool {
+        self.paused || self.show_help || self.share.is_some()
+    }
+
+    /// Replaces the levels with the one in an RLE string, as shared by
+    /// another player.
+    pub fn load_shared(&mut self, text: &str) -> Result<()> {
+        let level = levels::from_rle(text)?;
+        // Keyed by the board, so records for different shared levels don't mix.
+        self.collection = format!("rle:{}", levels::to_rle(&level)?);
+        self.levels = vec![level];
         Ok(())
     }
 
-    fn fixed_update(&mut self) {
-        let CurrentScreen::Game(level) = self.current_screen.borrow_mut() else {
-            return;
-        };
-        let mut next_grid: Grid<Cell> = level.level_state.clone();
-        assert_eq!(next_grid.bounds(), level.level_state.bounds());
-        for (i, row) in level.level_state.0.iter().enumerate() {
-            for (j, cell) in row.iter().enumerate() {
-                let spot = Coord(j as u16, i as u16);
-                next_grid[spot] = match cell {
-                    &Cell::Turret {
-                        direction,
-                        cooldown,
-                    } if cooldown > 0 => Cell::Turret {
-                        direction,
-                        cooldown: cooldown - 1,
-                    },
-                    &Cell::Turret {
-                        direction,
-                        cooldown: 0,
-                    } => Cell::Turret {
-                        direction,
-                        cooldown: 2,
-                    },
-                    &Cell::Bullet {
-                        direction,
-                        on_target,
-                    } => {
-                        match &level.level_state[spot + direction.into()] {
-                            Cell::Empty => {
-                                next_grid[spot + direction.into()] = Cell::Bullet {
-                                    direction,
-                                    on_target: *cell == Cell::Target,
-                                };
-                            }
-                            Cell::Player { on_target, hp } => {
-                                next_grid[spot + direction.into()] = if *hp > 1 {
-                                    Cell::Player {
-                                        hp: hp - 1,
-                                        on_target: *on_target,
-                                    }
-                                } else {
-                                    Cell::Empty
-                                };
-                            }
-                            _ => {}
-                        }
+    /// Text pasted into the terminal, which only matters while typing in a
+    /// level.
+    fn paste(&mut self, pasted: &str) {
+        if let CurrentScreen::Paste(text, _) = &mut self.current_screen {
+            text.push_str(pasted);
+        }
+    }
 
-                        if on_target {
-                            Cell::Target
-                        } else {
-                            Cell::Empty
+    fn process_paste_input(&mut self, key: KeyEvent) -> ControlFlow<bool> {
+        let CurrentScreen::Paste(text, _) = &mut self.current_screen else {
+            return Continue(());
+        };
+        if key.kind != KeyEventKind::Press {
+            return Continue(());
+        }
+        match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) => return Break(false),
+            (_, KeyCode::Esc) => self.current_screen = CurrentScreen::Menu(MenuItem::Paste),
+            (_, KeyCode::Backspace) => {
+                text.pop();
+            }
+            (_, KeyCode::Enter) => {
+                let text = text.clone();
+                match self.load_shared(&text) {
+                    Ok(()) => self.start_level(0),
+                    Err(report) => {
+                        if let CurrentScreen::Paste(_, error) = &mut self.current_screen {
+                            *error = Some(format!("{report:#}"));
                         }
                     }
-                    &other => other,
                 }
             }
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) => text.push(c),
+            _ => {}
+        }
+        Continue(())
+    }
+
+    fn fixed_update(&mut self) {
+        if let Some(direction) = self.pending_moves.pop_front() {
+            // The rest of the walk was planned from where this move led.
+            if !self.move_player(direction) {
+                self.pending_moves.clear();
+            }
+        }
+        let CurrentScreen::Game(level) = self.current_screen.borrow_mut() else {
+            return;
+        };
+        let trails = level.tick();
+        if self.config.bullet_trails {
+            for at in trails {
+                self.animations.trail(at);
+            }
         }
-        level.level_state = next_grid;
     }
 
-    fn update(&mut self) {
+    /// Moves on to the next screen once a level is won or lost.
+    pub fn update(&mut self) {
+        self.animations.prune(Instant::now());
         let CurrentScreen::Game(level) = &mut self.current_screen else {
             return;
         };
         if level.remaining_boxes == 0 {
-            self.next_level()
+            self.complete_level()
+        } else if !level.players.iter().any(|avatar| avatar.alive) {
+            self.pending_moves.clear();
+            self.current_screen = CurrentScreen::GameOver(level.index);
         }
     }
 
-    fn next_level(&mut self) {
-        self.current_screen = CurrentScreen::Game(self.select_level(self.next_level));
-        self.next_level += 1;
+    fn complete_level(&mut self) {
+        let CurrentScreen::Game(level) =
+            mem::replace(&mut self.current_screen, CurrentScreen::Ending)
+        else {
+            return;
+        };
+        let attempt = Record {
+            moves: level.move_counter,
+            pushes: level.push_counter,
+            time: level.time,
+        };
+        let key = match level.mode {
+            Mode::Forward => format!("{}#{}", self.collection, level.index),
+            Mode::Reverse => format!("{}#{}#reverse", self.collection, level.index),
+        };
+        let best = self.records.submit(key, attempt);
+        // Failing to save only loses the record, not worth stopping play for.
+        let _ = self.records.save();
+        let hp = level
+            .players
+            .iter()
+            .map(|avatar| match level.occupants[avatar.location] {
+                Occupant::Player { hp } if avatar.alive => hp,
+                _ => 0,
+            })
+            .sum();
+        self.current_screen = CurrentScreen::Complete(
+            Summary {
+                level: level.index,
+                attempt,
+                hp,
+                best,
+                par_moves: level.meta.par_moves,
+                par_pushes: level.meta.par_pushes,
+                solution: level.history,
+                show_solution: false,
+            },
+            CompleteItem::Next,
+        );
+    }
+
+    /// Starts level `index`, or shows the ending once there are none left.
+    pub fn start_level(&mut self, index: usize) {
+        self.pending_moves.clear();
+        self.selected_box = None;
+        self.ghost = None;
+        self.cursor = None;
+        self.animations.clear();
+        self.paused = false;
+        self.current_screen = match self.select_level(index) {
+            Some(level) => {
+                self.next_level = index + 1;
+                CurrentScreen::Game(Box::new(level))
+            }
+            None => {
+                self.next_level = 0;
+                CurrentScreen::Ending
+            }
+        };
     }
 
     fn process_input(&mut self, key: KeyEvent) -> ControlFlow<bool> {
         use CurrentScreen::*;
         use KeyBind::*;
-        match &self.current_screen {
+        // Typing a level needs every key, bound or not.
+        if let Paste(..) = self.current_screen {
+            return self.process_paste_input(key);
+        }
+        let action = self.config.keymap.action(key);
+        if self.share.is_some() {
+            if key.kind == KeyEventKind::Press {
+                self.share.take();
+            }
+            return Continue(());
+        }
+        if self.show_help {
+            if matches!(action, Help | Quit | Select) {
+                self.show_help = false;
+            }
+            return Continue(());
+        }
+        if action == Help {
+            self.show_help = true;
+            return Continue(());
+        }
+        match &mut self.current_screen {
             Menu(menu_item) => {
-                match key.into() {
+                match action {
                     Quit => return Break(false),
                     Up => {
                         self.current_screen = Menu(menu_item.prev());
@@ -204,20 +545,194 @@ impl App {
                     }
                     Select => match menu_item {
                         MenuItem::Quit => return Break(false),
-                        MenuItem::Options => {}
-                        MenuItem::Play => {
-                            self.current_screen = Game(self.select_level(self.next_level));
-                            self.next_level += 1;
+                        MenuItem::Options => {
+                            self.current_screen = Options(OptionItem::Animations);
+                        }
+                        MenuItem::Play => self.start_level(self.next_level),
+                        MenuItem::Levels => {
+                            let last = self.levels.len().saturating_sub(1);
+                            self.current_screen = LevelSelect(self.next_level.min(last));
+                        }
+                        MenuItem::Paste => {
+                            self.current_screen = Paste(String::new(), Default::default())
                         }
                     },
                     _ => {}
                 }
                 Continue(())
             }
+            LevelSelect(selected) => {
+                let count = self.levels.len();
+                match action {
+                    Quit => self.current_screen = Menu(MenuItem::Levels),
+                    Up => *selected = (*selected + count - 1) % count,
+                    Down => *selected = (*selected + 1) % count,
+                    Select => {
+                        let index = *selected;
+                        self.start_level(index);
+                    }
+                    _ => {}
+                }
+                Continue(())
+            }
+            Options(option_item) => {
+                match action {
+                    Quit => self.current_screen = Menu(MenuItem::Options),
+                    Up => {
+                        self.current_screen = Options(option_item.prev());
+                    }
+                    Down => {
+                        self.current_screen = Options(option_item.next());
+                    }
+                    Select | Left | Right => match option_item {
+                        OptionItem::Animations => self.config.animations ^= true,
+                        OptionItem::BulletTrails => self.config.bullet_trails ^= true,
+                        OptionItem::LockFlash => self.config.lock_flash ^= true,
+                        OptionItem::Glyphs => self.config.glyphs = self.config.glyphs.next(),
+                        OptionItem::RepeatHint => self.config.repeat_hint ^= true,
+                        OptionItem::Mode => self.config.mode = self.config.mode.next(),
+                        OptionItem::Back => self.current_screen = Menu(MenuItem::Options),
+                    },
+                    _ => {}
+                }
+                Continue(())
+            }
+            Complete(summary, item) => {
+                match action {
+                    Quit => self.current_screen = Menu(MenuItem::Play),
+                    Up => *item = item.prev(),
+                    Down => *item = item.next(),
+                    Select => match item {
+                        CompleteItem::Next => {
+                            let next = summary.level + 1;
+                            self.start_level(next);
+                        }
+                        CompleteItem::Replay => {
+                            let level = summary.level;
+                            self.start_level(level);
+                        }
+                        CompleteItem::Solution => summary.show_solution ^= true,
+                        CompleteItem::Menu => self.current_screen = Menu(MenuItem::Play),
+                    },
+                    _ => {}
+                }
+                Continue(())
+            }
+            // Handled before keys are mapped.
+            Paste(..) => Continue(()),
+            Ending => {
+                if let Quit | Select = action {
+                    self.current_screen = Menu(MenuItem::Play);
+                }
+                Continue(())
+            }
+            GameOver(index) => {
+                match action {
+                    Quit => self.current_screen = Menu(MenuItem::Play),
+                    Select => {
+                        let index = *index;
+                        self.start_level(index);
+                    }
+                    _ => {}
+                }
+                Continue(())
+            }
+            Game(_) if self.paused => {
+                match action {
+                    Pause | Select => self.paused = false,
+                    Quit => return Break(false),
+                    _ => {}
+                }
+                Continue(())
+            }
+            Game(_) if self.cursor.is_some() => {
+                match action {
+                    Up | Down | Left | Right | UpLeft | UpRight | DownLeft | DownRight => {
+                        if let (Game(level), Some(cursor)) = (&self.current_screen, self.cursor) {
+                            self.cursor = level
+                                .occupants
+                                .neighbour(cursor, action.into())
+                                .or(Some(cursor));
+                        }
+                    }
+                    Select => self.walk_to_cursor(),
+                    Pause => self.pause(),
+                    // Stops the walk too.
+                    Cursor | Quit => {
+                        self.cursor.take();
+                        self.pending_moves.clear();
+                    }
+                    Undo => {
+                        self.pending_moves.clear();
+                        self.undo();
+                    }
+                    _ => {}
+                }
+                Continue(())
+            }
+            Game(_) if self.ghost.is_some() => {
+                match action {
+                    Up | Down | Left | Right | UpLeft | UpRight | DownLeft | DownRight => {
+                        self.steer_box(action.into())
+                    }
+                    PickBox => self.pick_box(),
+                    Select => self.push_to_ghost(),
+                    Pause => self.pause(),
+                    Quit | Undo | Switch => {
+                        self.ghost.take();
+                        self.selected_box.take();
+                    }
+                    _ => {}
+                }
+                Continue(())
+            }
             Game(_) => {
-                let action = key.into();
                 match action {
-                    Up | Down | Left | Right => self.move_player(action.into()),
+                    Pause => self.pause(),
+                    PickBox => {
+                        self.pending_moves.clear();
+                        self.pick_box();
+                    }
+                    Cursor => {
+                        if let Game(level) = &self.current_screen {
+                            self.cursor = Some(level.player_location());
+                        }
+                    }
+                    Share => {
+                        if let Game(level) = &self.current_screen {
+                            self.share = Some(match levels::to_rle(level) {
+                                Ok(rle) => rle,
+                                Err(report) => format!("Can't share this level: {report}"),
+                            });
+                        }
+                    }
+                    Pull => {
+                        if let Game(level) = &mut self.current_screen {
+                            level.pulling ^= level.mode == Mode::Reverse;
+                        }
+                    }
+                    Select => {
+                        if let Game(level) = &mut self.current_screen {
+                            level.next_start();
+                        }
+                    }
+                    Switch => {
+                        if let Game(level) = &mut self.current_screen {
+                            level.switch_player();
+                        }
+                        self.pending_moves.clear();
+                        self.selected_box.take();
+                    }
+                    Undo => {
+                        self.pending_moves.clear();
+                        self.selected_box.take();
+                        self.undo();
+                    }
+                    Up | Down | Left | Right | UpLeft | UpRight | DownLeft | DownRight => {
+                        self.pending_moves.clear();
+                        self.selected_box.take();
+                        self.move_player(action.into());
+                    }
                     Quit => return Break(false),
                     _ => {}
                 }
@@ -226,113 +741,187 @@ impl App {
         }
     }
 
-    fn move_player(&mut self, direction: Direction) {
-        let CurrentScreen::Game(level) = &mut self.current_screen else {
+    /// Clicking a floor cell walks the player there. Clicking a box selects it,
+    /// and clicking (or dragging to) a destination pushes it there.
+    fn process_mouse(&mut self, mouse: MouseEvent, area: Rect) {
+        let CurrentScreen::Game(level) = &self.current_screen else {
             return;
         };
-
-        assert!(matches!(
-            level.level_state[level.player_location],
-            Cell::Player {
-                on_target: _,
-                hp: _
-            }
-        ));
-
-        let dir: Offset = direction.into();
-
-        let next_pos = level.player_location + dir;
-        let next_next_pos = level.player_location + dir * 2;
-
-        let grid = &level.level_state;
-        let mut next_grid = level.level_state.clone();
-
-        use Cell::*;
-        (
-            next_grid[level.player_location],
-            next_grid[next_pos],
-            next_grid[next_next_pos],
-        ) = match (
-            &grid[level.player_location],
-            &grid[next_pos],
-            &grid[next_next_pos],
-        ) {
-            (Player { on_target, hp }, Empty, _) => {
-                level.player_location = next_pos;
-                level.move_counter += 1;
-                (
-                    if *on_target { Target } else { Empty },
-                    Player {
-                        on_target: false,
-                        hp: *hp,
-                    },
-                    grid[next_next_pos],
-                )
+        let Some(clicked) = self.coord_at(area, mouse.column, mouse.row) else {
+            return;
+        };
+        // The pointer moving over the board is an event too, and shouldn't
+        // cancel anything.
+        if let MouseEventKind::Down(_) | MouseEventKind::Up(_) = mouse.kind {
+            self.cursor = None;
+            // A box picked from the keyboard isn't one to push with the mouse.
+            if self.ghost.take().is_some() {
+                self.selected_box = None;
             }
-            (Player { on_target, hp }, Target, _) => {
-                level.player_location = next_pos;
-                level.move_counter += 1;
-                (
-                    if *on_target { Target } else { Empty },
-                    Player {
-                        on_target: true,
-                        hp: *hp,
-                    },
-                    grid[next_next_pos],
-                )
+        }
+        let moves = match (mouse.kind, self.selected_box) {
+            (MouseEventKind::Down(MouseButton::Left), Some(selected)) if selected == clicked => {
+                self.selected_box = None;
+                return;
             }
-            (any, Box { locked: false }, Empty) => (*any, Empty, Box { locked: false }),
-            (any, Box { locked: false }, Target) => {
-                level.remaining_boxes -= 1;
-                (*any, Empty, Box { locked: true })
+            (
+                MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Up(MouseButton::Left),
+                Some(selected),
+            ) if selected != clicked => {
+                self.selected_box = None;
+                path::push_path(level, level.player_location(), selected, clicked)
             }
-            (any, Box { locked: true }, Empty) => {
-                level.remaining_boxes += 1;
-                (*any, Target, Box { locked: false })
+            (MouseEventKind::Down(MouseButton::Left), None) => match level.occupants[clicked] {
+                // Boxes can only be pulled in reverse mode.
+                Occupant::Box if level.mode == Mode::Forward => {
+                    self.selected_box = Some(clicked);
+                    return;
+                }
+                _ => path::walk_path(level, level.player_location(), clicked),
+            },
+            (MouseEventKind::Down(MouseButton::Right), _) => {
+                self.selected_box = None;
+                return;
             }
-            (any, Box { locked: true }, Target) => {
-                level.remaining_boxes += 1;
-                (*any, Target, Box { locked: true })
+            _ => return,
+        };
+        if let Some(moves) = moves {
+            self.pending_moves = moves.into();
+        }
+    }
+
+    /// Moves the player, returning whether they could.
+    fn move_player(&mut self, direction: Direction) -> bool {
+        let CurrentScreen::Game(level) = &mut self.current_screen else {
+            return false;
+        };
+        let Some(moved) = level.move_player(direction) else {
+            return false;
+        };
+        let to = level.player_location();
+        self.animate(moved, to);
+        true
+    }
+
+    /// Takes back the last action, which may have been several moves.
+    fn undo(&mut self) {
+        let CurrentScreen::Game(level) = &mut self.current_screen else {
+            return;
+        };
+        loop {
+            let more = level.undo_continues();
+            let Some(moved) = level.undo() else {
+                return;
+            };
+            if !more {
+                let to = level.player_location();
+                self.animate(moved, to);
+                return;
             }
-            (any, other, thing) => (*any, *other, *thing),
+        }
+    }
+
+    /// Sets the player walking to the cursor, a move a tick, keeping out of
+    /// the way of bullets.
+    fn walk_to_cursor(&mut self) {
+        let (CurrentScreen::Game(level), Some(cursor)) = (&self.current_screen, self.cursor) else {
+            return;
         };
+        if let Some(moves) = path::safe_walk_path(level, level.player_location(), cursor) {
+            self.pending_moves = moves.into();
+        }
+    }
 
-        level.level_state = next_grid;
+    /// Picks the box nearest the player to steer with the keys, or the next
+    /// box along if one is already picked.
+    fn pick_box(&mut self) {
+        let CurrentScreen::Game(level) = &self.current_screen else {
+            return;
+        };
+        // Boxes can only be pulled in reverse mode.
+        if level.mode == Mode::Reverse {
+            return;
+        }
+        let boxes: Vec<Coord> = level
+            .occupants
+            .iter()
+            .filter(|&(_, &occupant)| occupant == Occupant::Box)
+            .map(|(at, _)| at)
+            .collect();
+        let player = level.player_location();
+        let distance = |at: &&Coord| player.0.abs_diff(at.0) + player.1.abs_diff(at.1);
+        let picked = match (self.ghost.is_some(), self.selected_box) {
+            (true, Some(selected)) => boxes
+                .iter()
+                .position(|&at| at == selected)
+                .map(|i| &boxes[(i + 1) % boxes.len()]),
+            _ => boxes.iter().min_by_key(distance),
+        };
+        if let Some(&at) = picked {
+            self.selected_box = Some(at);
+            self.ghost = Some(Ghost { at, stuck: false });
+        }
     }
 
-    fn select_level(&mut self, level: usize) -> Level {
-        use Cell::{Empty as E, Target as T, Wall as W};
-        let b = Cell::Box { locked: false };
-        let l = Cell::Box { locked: true };
-        let p = Cell::Player {
-            on_target: false,
-            hp: 3,
+    /// Moves the picked box's ghost a square.
+    fn steer_box(&mut self, direction: Direction) {
+        let (CurrentScreen::Game(level), Some(ghost)) = (&self.current_screen, &mut self.ghost)
+        else {
+            return;
         };
-        match level {
-            0 => {
-                let mut grid = Grid(vec![vec![p, T, b]]);
-                grid.wrap(E);
-                grid.wrap(W);
-                grid.into()
-            }
-            1 => {
-                let mut grid = Grid(vec![
-                    vec![E, E, W, W, W, W, W, E],
-                    vec![W, W, W, E, E, E, W, E],
-                    vec![W, T, p, b, E, E, W, E],
-                    vec![W, W, W, E, b, T, W, E],
-                    vec![W, T, W, W, b, E, W, E],
-                    vec![W, E, W, E, T, E, W, W],
-                    vec![W, b, E, l, b, b, T, W],
-                    vec![W, E, E, E, T, E, E, W],
-                    vec![W, W, W, W, W, W, W, W],
-                ]);
-                grid.into()
-            }
-            _ => {
-                unimplemented!()
+        let Some(at) = level.occupants.neighbour(ghost.at, direction) else {
+            return;
+        };
+        if level.terrain[at] == Terrain::Wall {
+            return;
+        }
+        ghost.at = at;
+        ghost.stuck = false;
+    }
+
+    /// Pushes the picked box to its ghost, if there's a way, as one move to
+    /// undo.
+    fn push_to_ghost(&mut self) {
+        let (CurrentScreen::Game(level), Some(from), Some(ghost)) =
+            (&mut self.current_screen, self.selected_box, &mut self.ghost)
+        else {
+            return;
+        };
+        let Some(moves) = path::push_path(level, level.player_location(), from, ghost.at) else {
+            ghost.stuck = true;
+            return;
+        };
+        self.ghost = None;
+        self.selected_box = None;
+        if let Some(moved) = level.move_sequence(&moves).pop() {
+            let to = level.player_location();
+            self.animate(moved, to);
+        }
+    }
+
+    /// Slides whatever just moved, now that the player is on `to`.
+    fn animate(&mut self, moved: Moved, to: Coord) {
+        if let Some((from, to, locked)) = moved.moved_box {
+            if self.config.animations {
+                self.animations.tween(from, to, self.config.move_duration);
+            }
+            if locked && self.config.lock_flash {
+                self.animations.flash(to);
             }
         }
+        if self.config.animations {
+            self.animations
+                .tween(moved.from, to, self.config.move_duration);
+        }
+    }
+
+    fn select_level(&mut self, index: usize) -> Option<Level> {
+        let mut level = self.levels.get(index)?.clone();
+        if self.config.mode == Mode::Reverse {
+            level = level.reversed();
+        }
+        level.index = index;
+        Some(level)
     }
 }
 
@@ -340,170 +929,798 @@ impl Default for App {
     fn default() -> Self {
         Self {
             current_screen: CurrentScreen::Menu(MenuItem::Play),
+            config: Config::default(),
+            animations: Animations::default(),
             next_level: 0,
             timing_buffer: [Duration::ZERO; 30],
             timing_index: 0,
+            pending_moves: VecDeque::new(),
+            selected_box: None,
+            ghost: None,
+            cursor: None,
+            show_help: false,
+            share: None,
+            paused: false,
+            records: Records::load(),
+            levels: levels::builtin(),
+            collection: "builtin".to_owned(),
         }
     }
 }
 
-impl From<Grid<Cell>> for Level {
-    fn from(mut value: Grid<Cell>) -> Self {
-        value.wrap(Cell::Empty);
-        let Some(player_location) = value.get_player() else {
-            unreachable!()
-        };
+impl Level {
+    fn new(terrain: Grid<Terrain>, occupants: Grid<Occupant>) -> Self {
+        let hash = position::zobrist(&occupants);
+        let remaining_boxes = occupants
+            .iter()
+            .filter(|&(at, &occupant)| occupant == Occupant::Box && terrain[at] != Terrain::Target)
+            .count();
+        let players = occupants
+            .iter()
+            .filter(|(_, cell)| matches!(cell, Occupant::Player { .. }))
+            .map(|(location, _)| Avatar {
+                location,
+                alive: true,
+                moves: 0,
+                pushes: 0,
+                keys: vec![],
+                undo: vec![],
+            })
+            .collect();
         Level {
-            player_location,
-            remaining_boxes: value.count(Cell::Box { locked: false }),
-            level_state: value,
+            index: 0,
+            players,
+            active: 0,
+            remaining_boxes,
+            terrain,
+            occupants,
             move_counter: 0,
+            push_counter: 0,
+            history: String::new(),
+            time: Duration::ZERO,
+            meta: Metadata::default(),
+            hash,
+            seen: HashSet::from([hash]),
+            repeated: false,
+            mode: Mode::Forward,
+            pulling: false,
         }
     }
-}
 
-impl Grid<Cell> {
-    fn get_player(&self) -> Option<Coord> {
-        for (i, val) in self.0.iter().enumerate() {
-            for (j, who) in val.iter().enumerate() {
-                match *who {
-                    Cell::Player { on_target, hp } => {
-                        return Some(Coord(i as u16, j as u16));
+    /// Advances the turrets and bullets by one tick. Returns the squares
+    /// bullets moved out of, for drawing their trails.
+    pub fn tick(&mut self) -> Vec<Coord> {
+        // Walls and shut gates stop bullets.
+        let clear = |at: Coord| {
+            self.occupants[at] == Occupant::Empty
+                && !matches!(
+                    self.terrain[at],
+                    Terrain::Wall | Terrain::Gate { open: false }
+                )
+        };
+        let mut trails = vec![];
+        let mut next_grid: Grid<Occupant> = self.occupants.clone();
+        for (spot, &occupant) in self.occupants.iter() {
+            match occupant {
+                Occupant::Turret {
+                    direction,
+                    cooldown,
+                } if cooldown > 0 => {
+                    next_grid[spot] = Occupant::Turret {
+                        direction,
+                        cooldown: cooldown - 1,
+                    };
+                }
+                Occupant::Turret {
+                    direction,
+                    cooldown: 0,
+                } => {
+                    let ahead = self.occupants.neighbour(spot, direction);
+                    if let Some(next) = ahead.filter(|&next| clear(next)) {
+                        next_grid[next] = Occupant::Bullet { direction };
+                    }
+                    next_grid[spot] = Occupant::Turret {
+                        direction,
+                        cooldown: self.meta.fire_rate,
+                    };
+                }
+                Occupant::Bullet { direction } => {
+                    next_grid[spot] = Occupant::Empty;
+                    // Bullets leaving the grid just disappear.
+                    let ahead = self.occupants.neighbour(spot, direction);
+                    match ahead.map(|next| (next, self.occupants[next])) {
+                        Some((next, Occupant::Empty)) if clear(next) => {
+                            trails.push(spot);
+                            next_grid[next] = Occupant::Bullet { direction };
+                        }
+                        Some((next, Occupant::Player { hp })) => {
+                            next_grid[next] = if hp > 1 {
+                                Occupant::Player { hp: hp - 1 }
+                            } else {
+                                Occupant::Empty
+                            };
+                        }
+                        _ => {}
                     }
-                    _ => {}
                 }
+                _ => {}
             }
         }
-        None
+        self.occupants = next_grid;
+        self.update_gates();
+        for avatar in &mut self.players {
+            avatar.alive &= matches!(self.occupants[avatar.location], Occupant::Player { .. });
+        }
+        trails
+    }
+
+    /// Where the player being controlled is.
+    pub fn player_location(&self) -> Coord {
+        self.players[self.active].location
     }
-}
 
-impl<T: Copy + PartialEq> Grid<T> {
-    fn bounds(&self) -> (u16, u16) {
-        (self.0.len() as u16, self.0[0].len() as u16)
+    /// Hands control to the next player still standing.
+    pub fn switch_player(&mut self) {
+        let count = self.players.len();
+        if let Some(next) = (1..=count)
+            .map(|i| (self.active + i) % count)
+            .find(|&i| self.players[i].alive)
+        {
+            self.active = next;
+        }
     }
 
-    fn wrap(&mut self, with: T) {
-        let b = self.bounds();
-        let bar = vec![with; b.1 as usize + 2];
-        for mut i in &mut self.0 {
-            i.insert(0, with);
-            while i.len() < b.1 as usize + 2 {
-                i.push(with);
+    /// The same level set up for reverse mode: boxes start on the targets, and
+    /// the squares they started on become the targets.
+    pub fn reversed(&self) -> Level {
+        let (terrain, occupants) = (&self.terrain, &self.occupants);
+        let has_box = |at: Coord| occupants[at] == Occupant::Box;
+        let reversed_terrain =
+            Grid::from_fn(terrain.width(), terrain.height(), |at| match terrain[at] {
+                _ if has_box(at) => Terrain::Target,
+                Terrain::Target => Terrain::Plain,
+                other => other,
+            })
+            .with_topology(terrain.topology());
+        let mut reversed = Grid::from_fn(occupants.width(), occupants.height(), |at| {
+            match occupants[at] {
+                _ if terrain[at] == Terrain::Target => Occupant::Box,
+                // Turrets and bullets stay put, players are placed below.
+                Occupant::Player { .. } | Occupant::Box => Occupant::Empty,
+                other => other,
             }
+        })
+        .with_topology(occupants.topology());
+        for avatar in &self.players {
+            let Occupant::Player { hp } = occupants[avatar.location] else {
+                continue;
+            };
+            // A player who started on a target now shares it with a box, so
+            // they step out to the nearest free square.
+            let mut start = avatar.location;
+            let mut queue = VecDeque::from([start]);
+            let mut seen = HashSet::from([start]);
+            while let Some(at) = queue.pop_front() {
+                if reversed[at] == Occupant::Empty {
+                    start = at;
+                    break;
+                }
+                for (_, next) in reversed.neighbours(at) {
+                    if reversed_terrain[next] != Terrain::Wall && seen.insert(next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+            reversed[start] = Occupant::Player { hp };
+        }
+        Level {
+            index: self.index,
+            mode: Mode::Reverse,
+            ..Level::new(reversed_terrain, reversed)
         }
-        self.0.insert(0, bar.clone());
-        self.0.push(bar);
     }
 
-    fn get(&self, getting: T) -> Plural<Coord> {
-        let mut out = vec![];
-        for (i, val) in self.0.iter().enumerate() {
-            for (j, who) in val.iter().enumerate() {
-                if who == &getting {
-                    out.push(Coord(i as u16, j as u16));
+    /// Before the first pull of reverse mode the player may start anywhere, so
+    /// this moves them to the first free square (in reading order) after
+    /// their own that they couldn't walk to.
+    pub fn next_start(&mut self) {
+        if self.mode != Mode::Reverse || self.push_counter > 0 {
+            return;
+        }
+        let from = self.player_location();
+        let open = |at: Coord| at == from || self.is_open(at);
+        // Number the regions the player could walk around in, noting the
+        // ones that reach the edge of the board and so are outside.
+        let (width, height) = (self.occupants.width(), self.occupants.height());
+        let mut region = Grid::from_fn(width, height, |_| None);
+        let mut outside = HashSet::new();
+        let mut regions = 0;
+        for (start, _) in self.occupants.iter() {
+            if region[start].is_some() || !open(start) {
+                continue;
+            }
+            region[start] = Some(regions);
+            let mut queue = VecDeque::from([start]);
+            while let Some(at) = queue.pop_front() {
+                if at.0 == 0 || at.1 == 0 || at.0 == width - 1 || at.1 == height - 1 {
+                    outside.insert(regions);
+                }
+                for (_, next) in self.occupants.neighbours(at) {
+                    if region[next].is_none() && open(next) {
+                        region[next] = Some(regions);
+                        queue.push_back(next);
+                    }
                 }
             }
+            regions += 1;
         }
-        if out.is_empty() {
-            Plural::None
-        } else if out.len() == 1 {
-            Plural::One(out[0])
-        } else {
-            Plural::Many(out)
+        let free = |(at, _)| self.is_open(at).then_some(at);
+        let after = |Coord(x, y): Coord| (y, x) > (from.1, from.0);
+        let elsewhere = |&at: &Coord| {
+            region[at] != region[from] && region[at].is_some_and(|r| !outside.contains(&r))
+        };
+        let candidates: Vec<Coord> = self.occupants.iter().filter_map(free).collect();
+        let split = candidates.partition_point(|&at| !after(at));
+        let Some(to) = candidates[split..]
+            .iter()
+            .chain(&candidates[..split])
+            .copied()
+            .find(elsewhere)
+        else {
+            return;
+        };
+        if self.step_avatar(from, to) {
+            self.players[self.active].location = to;
+            self.seen = HashSet::from([self.hash]);
         }
     }
 
-    fn count(&self, getting: T) -> usize {
-        match self.get(getting) {
-            Plural::None => 0,
-            Plural::One(_) => 1,
-            Plural::Many(v) => v.len(),
+    /// Moves the player being controlled one step, pushing a box if there is
+    /// one in the way. In reverse mode boxes can't be pushed, but while
+    /// `pulling` the box behind the player follows them. Returns `None` if
+    /// nothing could move.
+    ///
+    /// Players and pushed boxes keep sliding across ice, and a player who
+    /// ends up on a teleporter comes out of its partner if that is free.
+    pub fn move_player(&mut self, direction: Direction) -> Option<Moved> {
+        use Occupant::*;
+        let from = self.player_location();
+        // Nobody left to move once a turret has finished the player off.
+        if !self.players[self.active].alive {
+            return None;
+        }
+        let next_pos = self.occupants.neighbour(from, direction)?;
+        if !self.can_enter(next_pos, direction, true) {
+            return None;
+        }
+        let keys = self.players[self.active].keys.clone();
+        let mut changed = vec![];
+
+        let mut moved_box = None;
+        let mut filled = None;
+        match (self.occupants[next_pos], self.mode) {
+            (Empty, _) => {}
+            (Box, Mode::Forward) => {
+                let (to, locked, hole) = self.push_box(next_pos, direction)?;
+                moved_box = Some((next_pos, to, locked));
+                filled = hole.then_some(to);
+            }
+            _ => return None,
+        }
+        if !self.step_avatar(from, next_pos) {
+            return None;
+        }
+        if self.pulling {
+            let behind = self.occupants.neighbour(from, direction.opposite());
+            if let Some(behind) = behind.filter(|&at| self.occupants[at] == Box) {
+                if self.can_enter(from, direction, false) {
+                    let locked = self
+                        .move_box(behind, from)
+                        .expect("the player just left that square");
+                    moved_box = Some((behind, from, locked));
+                }
+            }
         }
+        let mut to = next_pos;
+        self.arrive(to, &mut changed);
+        while let Some(next) = self.slide(to, direction, true) {
+            self.step_avatar(to, next);
+            to = next;
+            self.arrive(to, &mut changed);
+        }
+        if let Some(partner) = self.teleport(to) {
+            self.step_avatar(to, partner);
+            to = partner;
+            self.arrive(to, &mut changed);
+        }
+        self.repeated = !self.seen.insert(self.hash);
+
+        let pushed = moved_box.is_some() as usize;
+        self.move_counter += 1;
+        self.push_counter += pushed;
+        let avatar = &mut self.players[self.active];
+        avatar.location = to;
+        avatar.moves += 1;
+        avatar.pushes += pushed;
+        avatar.undo.push(Step {
+            from,
+            moved_box: moved_box.map(|(from, to, _)| (from, to)),
+            filled,
+            changed,
+            keys,
+            index: self.history.len(),
+            joined: false,
+        });
+        self.history.push(direction.lurd(pushed > 0));
+        // Whoever just stepped on or off a plate works the gates straight
+        // away, not at the next tick.
+        self.update_gates();
+        Some(Moved { from, moved_box })
     }
-}
 
-enum Plural<T> {
-    None,
-    One(T),
-    Many(Vec<T>),
-}
+    /// Makes a run of moves as one action, which undoes all at once. Stops at
+    /// the first move that is blocked.
+    pub fn move_sequence(&mut self, moves: &[Direction]) -> Vec<Moved> {
+        let mut done = vec![];
+        for &direction in moves {
+            let Some(moved) = self.move_player(direction) else {
+                break;
+            };
+            if !done.is_empty() {
+                if let Some(step) = self.players[self.active].undo.last_mut() {
+                    step.joined = true;
+                }
+            }
+            done.push(moved);
+        }
+        done
+    }
 
-impl From<KeyEvent> for KeyBind {
-    fn from(value: KeyEvent) -> Self {
-        use KeyCode::*;
-        use KeyEventKind::*;
+    /// Whether the next undo is part of a longer action.
+    pub fn undo_continues(&self) -> bool {
+        self.players[self.active]
+            .undo
+            .last()
+            .is_some_and(|step| step.joined)
+    }
 
-        if let (KeyModifiers::CONTROL, Char('c')) = (value.modifiers, value.code) {
-            return KeyBind::Quit;
+    /// Takes back the last move of the player being controlled, unless
+    /// another player has since moved something into the way.
+    pub fn undo(&mut self) -> Option<Moved> {
+        use Occupant::*;
+        let avatar = &self.players[self.active];
+        let step = avatar.undo.last()?.clone();
+        let at = avatar.location;
+        let free = |occupant: Occupant| occupant == Empty;
+        if !avatar.alive {
+            return None;
+        }
+        let moved_box = match step.moved_box {
+            // A pulled box sits where the player was, so it goes back first.
+            Some((box_from, box_to)) if box_to == step.from => {
+                let locked = self.move_box(box_to, box_from)?;
+                self.step_avatar(at, step.from);
+                Some((box_to, box_from, locked))
+            }
+            // The box fell into a hole, so dig it back out.
+            Some((box_from, box_to)) if step.filled.is_some() => {
+                if !free(self.occupants[step.from]) {
+                    return None;
+                }
+                self.step_avatar(at, step.from);
+                self.terrain[box_to] = Terrain::Hole;
+                self.remaining_boxes += (self.terrain[box_from] != Terrain::Target) as usize;
+                self.occupants[box_from] = Box;
+                self.hash ^= box_key(box_from);
+                Some((box_to, box_from, false))
+            }
+            Some((box_from, box_to)) => {
+                if !free(self.occupants[step.from]) || self.occupants[box_to] != Box {
+                    return None;
+                }
+                self.step_avatar(at, step.from);
+                let locked = self
+                    .move_box(box_to, box_from)
+                    .expect("the player just left that square");
+                Some((box_to, box_from, locked))
+            }
+            None => {
+                if !free(self.occupants[step.from]) {
+                    return None;
+                }
+                self.step_avatar(at, step.from);
+                None
+            }
+        };
+        self.repeated = false;
+        for &(at, terrain) in step.changed.iter().rev() {
+            self.terrain[at] = terrain;
+            self.hash ^= terrain_key(at);
         }
+        self.players[self.active].keys = step.keys;
 
-        if value.kind == Press {
-            match value.code {
-                Esc | Char('q') => KeyBind::Quit,
-                Up | Char('w') => KeyBind::Up,
-                Left | Char('a') => KeyBind::Left,
-                Down | Char('s') => KeyBind::Down,
-                Right | Char('d') => KeyBind::Right,
-                Enter | Char(' ') => KeyBind::Select,
-                _ => KeyBind::None,
+        let pushed = moved_box.is_some() as usize;
+        self.move_counter -= 1;
+        self.push_counter -= pushed;
+        let avatar = &mut self.players[self.active];
+        avatar.location = step.from;
+        avatar.moves -= 1;
+        avatar.pushes -= pushed;
+        avatar.undo.pop();
+        self.history.remove(step.index);
+        for later in self.players.iter_mut().flat_map(|avatar| &mut avatar.undo) {
+            if later.index > step.index {
+                later.index -= 1;
             }
-        } else {
-            KeyBind::None
         }
+        self.update_gates();
+        Some(Moved {
+            from: at,
+            moved_box,
+        })
     }
-}
 
-impl Add<Offset> for Coord {
-    type Output = Self;
+    /// Whether the player being controlled (or else a box) may move onto `at`
+    /// going `direction`, going by the floor there. One-way arrows only let
+    /// things across the way they point, doors need the right key, and holes
+    /// stop players but not boxes.
+    pub fn can_enter(&self, at: Coord, direction: Direction, player: bool) -> bool {
+        match self.terrain[at] {
+            Terrain::Wall => false,
+            Terrain::OneWay(only) => only == direction,
+            Terrain::Door(id) => player && self.players[self.active].keys.contains(&id),
+            Terrain::Gate { open } => open,
+            Terrain::Hole => !player,
+            _ => true,
+        }
+    }
 
-    fn add(self, rhs: Offset) -> Self::Output {
-        let x = if rhs.0 > 0 {
-            self.0 + rhs.0 as u16
-        } else {
-            self.0 - rhs.0.abs() as u16
+    /// The player being controlled has just reached `at`: pick up any key
+    /// there, or use one up opening a door. Records what the floor was in
+    /// `changed`.
+    fn arrive(&mut self, at: Coord, changed: &mut Vec<(Coord, Terrain)>) {
+        let keys = &mut self.players[self.active].keys;
+        match self.terrain[at] {
+            Terrain::Key(id) => keys.push(id),
+            Terrain::Door(id) => {
+                let used = keys.iter().position(|&key| key == id);
+                keys.remove(used.expect("doors only let in players with a key"));
+            }
+            _ => return,
+        }
+        changed.push((at, self.terrain[at]));
+        self.terrain[at] = Terrain::Plain;
+        self.hash ^= terrain_key(at);
+    }
+
+    /// Where something on ice at `at` slides to next, if anywhere.
+    fn slide(&self, at: Coord, direction: Direction, player: bool) -> Option<Coord> {
+        if self.terrain[at] != Terrain::Ice {
+            return None;
+        }
+        let next = self.occupants.neighbour(at, direction)?;
+        let free = self.occupants[next] == Occupant::Empty;
+        (free && self.can_enter(next, direction, player)).then_some(next)
+    }
+
+    /// Opens every gate while every pressure plate has a box or player on
+    /// it, and closes them again otherwise. Gates with something in the way
+    /// stay open.
+    pub fn update_gates(&mut self) {
+        let plates: Vec<Coord> = self
+            .terrain
+            .iter()
+            .filter(|(_, &terrain)| terrain == Terrain::Plate)
+            .map(|(at, _)| at)
+            .collect();
+        let pressed = !plates.is_empty()
+            && plates
+                .iter()
+                .all(|&at| matches!(self.occupants[at], Occupant::Box | Occupant::Player { .. }));
+        let gates: Vec<Coord> = self
+            .terrain
+            .iter()
+            .filter(|(_, terrain)| matches!(terrain, Terrain::Gate { .. }))
+            .map(|(at, _)| at)
+            .collect();
+        for at in gates {
+            let blocked = self.occupants[at] != Occupant::Empty;
+            self.terrain[at] = Terrain::Gate {
+                open: pressed || blocked,
+            };
+        }
+    }
+
+    /// The free partner of the teleporter on `at`, if it is one.
+    fn teleport(&self, at: Coord) -> Option<Coord> {
+        let Terrain::Teleporter(pair) = self.terrain[at] else {
+            return None;
         };
-        let y = if rhs.1 > 0 {
-            self.1 + rhs.1 as u16
-        } else {
-            self.1 - rhs.1.abs() as u16
+        self.terrain
+            .iter()
+            .find(|&(other, &terrain)| other != at && terrain == Terrain::Teleporter(pair))
+            .map(|(other, _)| other)
+            .filter(|&other| self.occupants[other] == Occupant::Empty)
+    }
+
+    /// Pushes the box on `from` one step, then on across any ice. Returns
+    /// where it stopped, whether it just locked onto a target, and whether it
+    /// fell into a hole there.
+    fn push_box(&mut self, from: Coord, direction: Direction) -> Option<(Coord, bool, bool)> {
+        let mut to = self.occupants.neighbour(from, direction)?;
+        if !self.can_enter(to, direction, false) {
+            return None;
+        }
+        let mut locked = self.move_box(from, to)?;
+        while self.terrain[to] != Terrain::Hole {
+            let Some(next) = self.slide(to, direction, false) else {
+                break;
+            };
+            locked = self
+                .move_box(to, next)
+                .expect("slides only onto free squares");
+            to = next;
+        }
+        if self.terrain[to] == Terrain::Hole {
+            // The hole swallows the box and is filled in.
+            self.remaining_boxes -= 1;
+            self.occupants[to] = Occupant::Empty;
+            self.terrain[to] = Terrain::Plain;
+            self.hash ^= box_key(to);
+            return Some((to, false, true));
+        }
+        Some((to, locked, false))
+    }
+
+    /// The mechanics the board actually uses.
+    pub fn mechanics(&self) -> Vec<Mechanic> {
+        let turrets = self
+            .occupants
+            .iter()
+            .any(|(_, occupant)| matches!(occupant, Occupant::Turret { .. }));
+        let tiles: Vec<Mechanic> = self
+            .terrain
+            .iter()
+            .filter_map(|(_, terrain)| terrain.mechanic())
+            .collect();
+        Mechanic::iter()
+            .filter(|mechanic| match mechanic {
+                Mechanic::Turrets => turrets,
+                _ => tiles.contains(mechanic),
+            })
+            .collect()
+    }
+
+    /// Whether a player could stand on `at` right now, ignoring special floor.
+    pub fn is_open(&self, at: Coord) -> bool {
+        self.occupants[at] == Occupant::Empty && self.terrain[at] != Terrain::Wall
+    }
+
+    /// Moves whoever is on `from` to the free square `to`. Returns whether
+    /// anything moved.
+    fn step_avatar(&mut self, from: Coord, to: Coord) -> bool {
+        use Occupant::*;
+        let (Player { hp }, Empty) = (self.occupants[from], self.occupants[to]) else {
+            return false;
         };
-        Self(x, y)
+        self.occupants[from] = Empty;
+        self.occupants[to] = Player { hp };
+        self.hash ^= player_key(from) ^ player_key(to);
+        true
+    }
+
+    /// Moves the box on `from` to the free square `to`, returning whether it
+    /// just locked onto a target, or `None` if `to` isn't free.
+    fn move_box(&mut self, from: Coord, to: Coord) -> Option<bool> {
+        use Occupant::*;
+        if (self.occupants[from], self.occupants[to]) != (Box, Empty) {
+            return None;
+        }
+        let locked = self.terrain[from] == Terrain::Target;
+        let onto_target = self.terrain[to] == Terrain::Target;
+        match (locked, onto_target) {
+            (false, true) => self.remaining_boxes -= 1,
+            (true, false) => self.remaining_boxes += 1,
+            _ => {}
+        }
+        self.occupants[from] = Empty;
+        self.occupants[to] = Box;
+        self.hash ^= box_key(from) ^ box_key(to);
+        Some(onto_target && !locked)
+    }
+}
+
+/// Builds a level from its terrain and occupant layers, wrapping both in a
+/// margin of floor.
+impl TryFrom<(Grid<Terrain>, Grid<Occupant>)> for Level {
+    type Error = Report;
+
+    fn try_from((mut terrain, mut occupants): (Grid<Terrain>, Grid<Occupant>)) -> Result<Self> {
+        terrain.wrap(Terrain::Plain);
+        occupants.wrap(Occupant::Empty);
+        if occupants.get_player().is_none() {
+            bail!("the level has nowhere for the player to start");
+        }
+        let mut level = Level::new(terrain, occupants);
+        level.update_gates();
+        Ok(level)
+    }
+}
+
+impl TryFrom<Saved> for Level {
+    type Error = Report;
+
+    fn try_from(saved: Saved) -> Result<Self> {
+        let (terrain, occupants) = (&saved.terrain, &saved.occupants);
+        if (terrain.width(), terrain.height(), terrain.topology())
+            != (occupants.width(), occupants.height(), occupants.topology())
+        {
+            bail!("the terrain and occupants grids have different shapes");
+        }
+        // Levels from files can only name these, so the rest of the game
+        // counts on it.
+        for (Coord(x, y), &tile) in terrain.iter() {
+            let fits = match tile {
+                Terrain::Key(id) | Terrain::Door(id) => id <= 4,
+                Terrain::Teleporter(pair) => (1..=9).contains(&pair),
+                _ => true,
+            };
+            if !fits {
+                bail!(
+                    "the key, door or teleporter at row {y}, column {x} is numbered out of range"
+                );
+            }
+        }
+        for pair in 1..=9 {
+            let count = terrain.count(Terrain::Teleporter(pair));
+            if count != 0 && count != 2 {
+                bail!("there are {count} teleporters numbered {pair}, expected two");
+            }
+        }
+        if let Some(avatar) = saved
+            .players
+            .iter()
+            .find(|avatar| occupants.get(avatar.location).is_none())
+        {
+            let Coord(x, y) = avatar.location;
+            bail!("a player is off the board at row {y}, column {x}");
+        }
+        let mut level = Level::new(saved.terrain, saved.occupants);
+        if !saved.players.is_empty() {
+            let standing = |avatar: &Avatar| {
+                matches!(
+                    level.occupants.get(avatar.location),
+                    Some(Occupant::Player { .. })
+                )
+            };
+            let alive = saved.players.iter().filter(|avatar| avatar.alive);
+            if alive.clone().count() != level.players.len() || !alive.clone().all(standing) {
+                bail!("the players don't match where the board has them");
+            }
+            level.players = saved.players;
+        }
+        if level.players.is_empty() {
+            bail!("the level has nowhere for the player to start");
+        }
+        if !level
+            .players
+            .get(saved.active)
+            .is_some_and(|avatar| avatar.alive)
+        {
+            bail!("player {} isn't there to move", saved.active + 1);
+        }
+        Ok(Level {
+            active: saved.active,
+            move_counter: saved.move_counter,
+            push_counter: saved.push_counter,
+            history: saved.history,
+            time: saved.time,
+            meta: saved.meta,
+            mode: saved.mode,
+            ..level
+        })
     }
 }
 
-impl Sub<Offset> for Coord {
-    type Output = Self;
+impl Occupant {
+    /// Whether this occupant animates between squares when it moves.
+    pub fn is_sliding(self) -> bool {
+        matches!(self, Occupant::Player { .. } | Occupant::Box)
+    }
+}
 
-    fn sub(self, rhs: Offset) -> Self::Output {
-        self.add(-rhs)
+impl Grid<Occupant> {
+    fn get_player(&self) -> Option<Coord> {
+        self.iter()
+            .find(|(_, cell)| matches!(cell, Occupant::Player { .. }))
+            .map(|(at, _)| at)
     }
 }
 
-impl Neg for Offset {
-    type Output = Offset;
+impl Terrain {
+    /// The mechanic this floor belongs to, if it is special.
+    pub fn mechanic(self) -> Option<Mechanic> {
+        match self {
+            Terrain::Plain | Terrain::Wall | Terrain::Target => None,
+            Terrain::Ice => Some(Mechanic::Ice),
+            Terrain::OneWay(_) => Some(Mechanic::OneWay),
+            Terrain::Hole => Some(Mechanic::Holes),
+            Terrain::Teleporter(_) => Some(Mechanic::Teleporters),
+            Terrain::Key(_) | Terrain::Door(_) => Some(Mechanic::Keys),
+            Terrain::Plate | Terrain::Gate { .. } => Some(Mechanic::Gates),
+        }
+    }
+}
 
-    fn neg(self) -> Self::Output {
-        self * -1
+impl Mechanic {
+    /// What level files call this mechanic.
+    pub fn name(self) -> &'static str {
+        match self {
+            Mechanic::Turrets => "turrets",
+            Mechanic::Ice => "ice",
+            Mechanic::OneWay => "one-way",
+            Mechanic::Holes => "holes",
+            Mechanic::Teleporters => "teleporters",
+            Mechanic::Keys => "keys",
+            Mechanic::Gates => "gates",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Mechanic::iter().find(|mechanic| mechanic.name().eq_ignore_ascii_case(name))
     }
 }
 
-impl Mul<i16> for Offset {
-    type Output = Self;
+impl Direction {
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::UpLeft => Direction::DownRight,
+            Direction::UpRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::UpRight,
+            Direction::DownRight => Direction::UpLeft,
+        }
+    }
 
-    fn mul(self, rhs: i16) -> Self::Output {
-        Self(self.0.saturating_mul(rhs), self.1.saturating_mul(rhs))
+    /// This move in LURD notation. The diagonals of hex boards are written
+    /// with the keys around `w`: `q`, `e`, `z` and `c`.
+    pub fn lurd(self, push: bool) -> char {
+        let c = match self {
+            Direction::Left => 'l',
+            Direction::Up => 'u',
+            Direction::Right => 'r',
+            Direction::Down => 'd',
+            Direction::UpLeft => 'q',
+            Direction::UpRight => 'e',
+            Direction::DownLeft => 'z',
+            Direction::DownRight => 'c',
+        };
+        if push {
+            c.to_ascii_uppercase()
+        } else {
+            c
+        }
     }
 }
 
-impl From<Direction> for Offset {
-    fn from(value: Direction) -> Self {
-        use Direction::*;
-        match value {
-            Up => Self(0, -1),
-            Down => Self(0, 1),
-            Right => Self(1, 0),
-            Left => Self(-1, 0),
+impl TryFrom<char> for Direction {
+    type Error = char;
+
+    /// Reads a LURD move, whether or not it is a push.
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value.to_ascii_lowercase() {
+            'l' => Ok(Direction::Left),
+            'u' => Ok(Direction::Up),
+            'r' => Ok(Direction::Right),
+            'd' => Ok(Direction::Down),
+            'q' => Ok(Direction::UpLeft),
+            'e' => Ok(Direction::UpRight),
+            'z' => Ok(Direction::DownLeft),
+            'c' => Ok(Direction::DownRight),
+            _ => Err(value),
         }
     }
 }
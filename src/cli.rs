@@ -0,0 +1,329 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use clap::{builder::RangedI64ValueParser, Parser, Subcommand};
+use color_eyre::{
+    eyre::{bail, eyre, WrapErr},
+    Result,
+};
+use serde::Serialize;
+
+use crate::app::Level;
+use crate::enums::Direction;
+use crate::generate::generate;
+use crate::levels;
+use crate::solver::{self, Outcome};
+use crate::validate;
+
+#[derive(Parser)]
+#[command(version, about = "Sokoban in the terminal")]
+pub struct Cli {
+    /// Settings file to use instead of the defaults.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Jump straight into a level, from the built-in set or a level file.
+    Play {
+        /// A level file, or `-` to read an RLE level string from standard
+        /// input.
+        file: Option<PathBuf>,
+        /// Which level to start on, counting from 1.
+        #[arg(long, default_value_t = 1)]
+        level: usize,
+        /// Start from the solved position and pull the boxes back.
+        #[arg(long)]
+        reverse: bool,
+    },
+    /// Print a solution for each level in a file.
+    Solve {
+        file: PathBuf,
+        /// Only solve this level, counting from 1.
+        #[arg(long)]
+        level: Option<usize>,
+        /// Give up on a level after exploring this many positions.
+        #[arg(long, default_value_t = 1_000_000)]
+        max_nodes: usize,
+    },
+    /// Time the solver on every level in a file, to spot it getting slower.
+    Bench {
+        file: PathBuf,
+        /// Give up on a level after exploring this many positions.
+        #[arg(long, default_value_t = 1_000_000)]
+        max_nodes: usize,
+        /// Give up on a level after this many seconds.
+        #[arg(long, default_value_t = 10.0)]
+        seconds: f64,
+        /// Also write the results as JSON to this file, or to standard output
+        /// in place of the table if it's `-`.
+        #[arg(long, value_name = "PATH")]
+        json: Option<PathBuf>,
+    },
+    /// Check that a LURD solution, or a file containing one, solves a level.
+    Verify {
+        file: PathBuf,
+        solution: String,
+        #[arg(long, default_value_t = 1)]
+        level: usize,
+    },
+    /// Print a random level that is guaranteed to be solvable.
+    Generate {
+        /// Defaults to the current time.
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(long, default_value_t = 10, value_parser = side())]
+        width: u16,
+        #[arg(long, default_value_t = 8, value_parser = side())]
+        height: u16,
+        #[arg(long, default_value_t = 3)]
+        boxes: usize,
+    },
+    /// Rewrite a level file in the format named by the output's extension
+    /// (.xsb or .json), or to standard output as XSB.
+    Convert {
+        input: PathBuf,
+        output: Option<PathBuf>,
+        /// Drop floor outside the walls, wall in floor nobody can reach, and
+        /// turn every board to a standard orientation.
+        #[arg(long)]
+        normalise: bool,
+    },
+    /// Look for mistakes in every level in a file, such as missing targets or
+    /// gaps in the walls.
+    Check { file: PathBuf },
+}
+
+/// Looks up a level by its 1-based number.
+/// The sizes `generate` accepts. Smaller rooms have no space inside them.
+fn side() -> RangedI64ValueParser<u16> {
+    RangedI64ValueParser::new().range(4..=levels::MAX_SIDE as i64)
+}
+
+pub fn pick(levels: &[Level], number: usize) -> Result<&Level> {
+    number
+        .checked_sub(1)
+        .and_then(|index| levels.get(index))
+        .ok_or_else(|| eyre!("there is no level {number}, only 1 to {}", levels.len()))
+}
+
+pub fn solve(file: &Path, only: Option<usize>, max_nodes: usize) -> Result<()> {
+    let levels = levels::load(file)?;
+    let chosen: Vec<(usize, &Level)> = match only {
+        Some(number) => vec![(number, pick(&levels, number)?)],
+        None => levels.iter().enumerate().map(|(i, l)| (i + 1, l)).collect(),
+    };
+    for (number, level) in chosen {
+        match solver::solve(level, max_nodes) {
+            Outcome::Solved(solution) => {
+                let pushes = solution.chars().filter(char::is_ascii_uppercase).count();
+                println!(
+                    "Level {number}: {solution} ({} moves, {pushes} pushes)",
+                    solution.len()
+                );
+            }
+            Outcome::Unsolvable => println!("Level {number}: no solution"),
+            Outcome::GaveUp => println!("Level {number}: gave up after {max_nodes} positions"),
+            Outcome::Unsupported(what) => println!("Level {number}: can't solve {what}"),
+        }
+    }
+    Ok(())
+}
+
+/// How the solver did on one level, as `bench` reports it.
+#[derive(Serialize)]
+struct BenchLevel {
+    level: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// `solved`, `unsolvable`, `gave up` or `unsupported`.
+    outcome: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    moves: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pushes: Option<usize>,
+    nodes: usize,
+    seconds: f64,
+    nodes_per_second: f64,
+    memory_bytes: usize,
+}
+
+#[derive(Serialize)]
+struct BenchReport {
+    file: String,
+    max_nodes: usize,
+    seconds_per_level: f64,
+    solved: usize,
+    nodes: usize,
+    seconds: f64,
+    nodes_per_second: f64,
+    peak_memory_bytes: usize,
+    levels: Vec<BenchLevel>,
+}
+
+pub fn bench(file: &Path, max_nodes: usize, seconds: f64, json: Option<&Path>) -> Result<()> {
+    let levels = levels::load(file)?;
+    let budget = Duration::try_from_secs_f64(seconds)
+        .map_err(|_| eyre!("{seconds} isn't a number of seconds"))?;
+    let table = json.is_none_or(|path| path != Path::new("-"));
+    if table {
+        println!(
+            "{:>5}  {:<11} {:>7} {:>7} {:>10} {:>8} {:>10} {:>9}",
+            "Level", "Result", "Moves", "Pushes", "Nodes", "Time", "Nodes/s", "Memory"
+        );
+    }
+    let mut results = vec![];
+    for (i, level) in levels.iter().enumerate() {
+        let started = Instant::now();
+        let (outcome, stats) = solver::solve_within(level, max_nodes, Some(budget));
+        let elapsed = started.elapsed().as_secs_f64();
+        let (name, solution) = match outcome {
+            Outcome::Solved(solution) => ("solved", Some(solution)),
+            Outcome::Unsolvable => ("unsolvable", None),
+            Outcome::GaveUp => ("gave up", None),
+            Outcome::Unsupported(_) => ("unsupported", None),
+        };
+        let result = BenchLevel {
+            level: i + 1,
+            title: level.meta.title.clone(),
+            outcome: name,
+            moves: solution.as_ref().map(String::len),
+            pushes: solution
+                .as_ref()
+                .map(|solution| solution.chars().filter(char::is_ascii_uppercase).count()),
+            nodes: stats.expanded,
+            seconds: elapsed,
+            nodes_per_second: stats.expanded as f64 / elapsed.max(f64::EPSILON),
+            memory_bytes: stats.memory,
+        };
+        if table {
+            let count = |n: Option<usize>| n.map_or("-".to_owned(), |n| n.to_string());
+            println!(
+                "{:>5}  {:<11} {:>7} {:>7} {:>10} {:>7.2}s {:>10.0} {:>6.1} MB",
+                result.level,
+                result.outcome,
+                count(result.moves),
+                count(result.pushes),
+                result.nodes,
+                result.seconds,
+                result.nodes_per_second,
+                result.memory_bytes as f64 / 1e6
+            );
+        }
+        results.push(result);
+    }
+
+    let nodes = results.iter().map(|result| result.nodes).sum();
+    let elapsed = results.iter().map(|result| result.seconds).sum::<f64>();
+    let report = BenchReport {
+        file: file.display().to_string(),
+        max_nodes,
+        seconds_per_level: seconds,
+        solved: results.iter().filter(|r| r.outcome == "solved").count(),
+        nodes,
+        seconds: elapsed,
+        nodes_per_second: nodes as f64 / elapsed.max(f64::EPSILON),
+        peak_memory_bytes: results.iter().map(|r| r.memory_bytes).max().unwrap_or(0),
+        levels: results,
+    };
+    if table {
+        println!(
+            "Solved {} of {} in {:.2}s, {:.0} nodes/s, peak memory {:.1} MB",
+            report.solved,
+            report.levels.len(),
+            report.seconds,
+            report.nodes_per_second,
+            report.peak_memory_bytes as f64 / 1e6
+        );
+    }
+    if let Some(path) = json {
+        let text = serde_json::to_string_pretty(&report)?;
+        if table {
+            fs::write(path, text).wrap_err_with(|| format!("couldn't write {}", path.display()))?;
+        } else {
+            println!("{text}");
+        }
+    }
+    Ok(())
+}
+
+pub fn verify(file: &Path, solution: &str, number: usize) -> Result<()> {
+    let levels = levels::load(file)?;
+    let mut level = pick(&levels, number)?.clone();
+    let solution = match fs::read_to_string(solution) {
+        Ok(text) => text,
+        Err(_) => solution.to_owned(),
+    };
+    for (i, c) in solution.chars().filter(|c| !c.is_whitespace()).enumerate() {
+        let direction = Direction::try_from(c)
+            .map_err(|c| eyre!("move {} is {c:?}, not one of LURD", i + 1))?;
+        if level.move_player(direction).is_none() {
+            bail!("move {} ({c}) is blocked", i + 1);
+        }
+    }
+    if level.remaining_boxes > 0 {
+        bail!(
+            "not solved, {} boxes are still off target",
+            level.remaining_boxes
+        );
+    }
+    println!(
+        "Solved in {} moves and {} pushes",
+        level.move_counter, level.push_counter
+    );
+    Ok(())
+}
+
+pub fn print_generated(seed: u64, width: u16, height: u16, boxes: usize) {
+    let level = generate(seed, width, height, boxes);
+    print!("; Generated from seed {seed}\n{}", levels::to_xsb(&[level]));
+}
+
+pub fn check(file: &Path) -> Result<()> {
+    let boards = levels::load_boards(file)?;
+    let mut failed = 0;
+    for (i, (terrain, occupants)) in boards.iter().enumerate() {
+        let problems = validate::validate(terrain, occupants);
+        if problems.is_empty() {
+            println!("Level {}: ok", i + 1);
+        }
+        for problem in &problems {
+            println!("Level {} {problem}", i + 1);
+        }
+        failed += usize::from(!problems.is_empty());
+    }
+    if failed > 0 {
+        bail!("{failed} of {} levels have problems", boards.len());
+    }
+    Ok(())
+}
+
+pub fn convert(input: &Path, output: Option<&Path>, normalise: bool) -> Result<()> {
+    let mut levels = levels::load(input)?;
+    if normalise {
+        levels = levels
+            .iter()
+            .map(validate::normalise)
+            .collect::<Result<_>>()?;
+    }
+    let Some(output) = output else {
+        print!("{}", levels::to_xsb(&levels));
+        return Ok(());
+    };
+    let text = match output.extension().and_then(|ext| ext.to_str()) {
+        Some("xsb" | "sok" | "txt") => levels::to_xsb(&levels),
+        Some("json") => levels::to_json(&levels),
+        _ => bail!(
+            "can't tell what format {} should be, try .xsb or .json",
+            output.display()
+        ),
+    };
+    fs::write(output, text).wrap_err_with(|| format!("couldn't write {}", output.display()))
+}
@@ -0,0 +1,190 @@
+use std::{collections::BTreeMap, fs, path::Path, time::Duration};
+
+use color_eyre::{
+    eyre::{bail, WrapErr},
+    Result,
+};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use serde::Deserialize;
+
+use crate::enums::{GlyphSet, KeyBind, Mode};
+
+/// Player-tunable settings, changed from the options screen.
+pub struct Config {
+    /// Slide the player and boxes between cells rather than jumping.
+    pub animations: bool,
+    pub bullet_trails: bool,
+    pub lock_flash: bool,
+    pub move_duration: Duration,
+    pub glyphs: GlyphSet,
+    /// Point out when a move returns to a position seen before.
+    pub repeat_hint: bool,
+    pub mode: Mode,
+    pub keymap: Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            animations: true,
+            bullet_trails: true,
+            lock_flash: true,
+            move_duration: Duration::from_millis(80),
+            glyphs: GlyphSet::default(),
+            repeat_hint: true,
+            mode: Mode::default(),
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+/// The settings file. Anything left out keeps its default.
+///
+/// ```json
+/// {
+///     "animations": false,
+///     "move_duration_ms": 50,
+///     "glyphs": "Ascii",
+///     "keymap": { "k": "Up", "j": "Down", "h": "Left", "l": "Right", "Space": "Select" }
+/// }
+/// ```
+///
+/// A `keymap` replaces the default bindings entirely.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    animations: Option<bool>,
+    bullet_trails: Option<bool>,
+    lock_flash: Option<bool>,
+    move_duration_ms: Option<u64>,
+    glyphs: Option<GlyphSet>,
+    repeat_hint: Option<bool>,
+    mode: Option<Mode>,
+    keymap: Option<BTreeMap<String, KeyBind>>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .wrap_err_with(|| format!("couldn't read config file {}", path.display()))?;
+        let file: ConfigFile = serde_json::from_str(&text)
+            .wrap_err_with(|| format!("{} isn't a valid config file", path.display()))?;
+        let default = Config::default();
+        let keymap = match file.keymap {
+            Some(bindings) => Keymap::from_names(bindings)?,
+            None => default.keymap,
+        };
+        Ok(Self {
+            animations: file.animations.unwrap_or(default.animations),
+            bullet_trails: file.bullet_trails.unwrap_or(default.bullet_trails),
+            lock_flash: file.lock_flash.unwrap_or(default.lock_flash),
+            move_duration: file
+                .move_duration_ms
+                .map_or(default.move_duration, Duration::from_millis),
+            glyphs: file.glyphs.unwrap_or(default.glyphs),
+            repeat_hint: file.repeat_hint.unwrap_or(default.repeat_hint),
+            mode: file.mode.unwrap_or(default.mode),
+            keymap,
+        })
+    }
+}
+
+/// Which key does what, in the order they are listed on the help screen.
+/// Ctrl-C quits no matter what is bound.
+pub struct Keymap(pub Vec<(KeyCode, KeyBind)>);
+
+impl Keymap {
+    /// Builds a keymap from key names like `"w"`, `"Up"` or `"Space"`.
+    fn from_names(bindings: BTreeMap<String, KeyBind>) -> Result<Self> {
+        let mut keys = vec![];
+        for (name, action) in bindings {
+            let Some(code) = key_from_name(&name) else {
+                bail!("unknown key {name:?} in keymap");
+            };
+            keys.push((code, action));
+        }
+        keys.sort_by_key(|&(_, action)| action);
+        Ok(Self(keys))
+    }
+
+    pub fn action(&self, key: KeyEvent) -> KeyBind {
+        if let (KeyModifiers::CONTROL, KeyCode::Char('c')) = (key.modifiers, key.code) {
+            return KeyBind::Quit;
+        }
+        if key.kind != KeyEventKind::Press {
+            return KeyBind::None;
+        }
+        self.0
+            .iter()
+            .find(|(code, _)| *code == key.code)
+            .map_or(KeyBind::None, |&(_, action)| action)
+    }
+
+    pub fn keys_for(&self, action: KeyBind) -> Vec<KeyCode> {
+        self.0
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|&(code, _)| code)
+            .collect()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use KeyCode::*;
+        Self(vec![
+            (Up, KeyBind::Up),
+            (Char('w'), KeyBind::Up),
+            (Left, KeyBind::Left),
+            (Char('a'), KeyBind::Left),
+            (Down, KeyBind::Down),
+            (Char('s'), KeyBind::Down),
+            (Right, KeyBind::Right),
+            (Char('d'), KeyBind::Right),
+            (Home, KeyBind::UpLeft),
+            (Char('7'), KeyBind::UpLeft),
+            (PageUp, KeyBind::UpRight),
+            (Char('9'), KeyBind::UpRight),
+            (End, KeyBind::DownLeft),
+            (Char('1'), KeyBind::DownLeft),
+            (PageDown, KeyBind::DownRight),
+            (Char('3'), KeyBind::DownRight),
+            (Char('x'), KeyBind::Pull),
+            (Char('u'), KeyBind::Undo),
+            (Backspace, KeyBind::Undo),
+            (Tab, KeyBind::Switch),
+            (Char('b'), KeyBind::PickBox),
+            (Char('g'), KeyBind::Cursor),
+            (Enter, KeyBind::Select),
+            (Char(' '), KeyBind::Select),
+            (Char('p'), KeyBind::Pause),
+            (Char('?'), KeyBind::Help),
+            (Char('c'), KeyBind::Share),
+            (Esc, KeyBind::Quit),
+            (Char('q'), KeyBind::Quit),
+        ])
+    }
+}
+
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    Some(match name {
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Space" => KeyCode::Char(' '),
+        "Tab" => KeyCode::Tab,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Backspace" => KeyCode::Backspace,
+        _ => return None,
+    })
+}
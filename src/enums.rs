@@ -1,23 +1,42 @@
+use serde::{Deserialize, Serialize};
 use strum::EnumIter;
 
-use crate::app::{Coord, Level, Offset};
+use crate::app::{Level, Summary};
 
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Deserialize)]
 pub enum KeyBind {
     Quit,
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+    Pull,
+    Undo,
+    Switch,
+    PickBox,
+    Cursor,
     Select,
+    Pause,
+    Help,
+    Share,
     None,
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, EnumIter, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
     Left,
     Right,
+    /// The diagonals only exist on hex boards.
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
 }
 
 impl From<KeyBind> for Direction {
@@ -29,6 +48,10 @@ impl From<KeyBind> for Direction {
             Down => D::Down,
             Left => D::Left,
             Right => D::Right,
+            UpLeft => D::UpLeft,
+            UpRight => D::UpRight,
+            DownLeft => D::DownLeft,
+            DownRight => D::DownRight,
             _ => panic!("Shouldn't convert other keybind to direction."),
         }
     }
@@ -36,34 +59,111 @@ impl From<KeyBind> for Direction {
 
 pub enum CurrentScreen {
     Menu(MenuItem),
-    Game(Level),
+    /// Picking a level to play, with the index of the one highlighted.
+    LevelSelect(usize),
+    Options(OptionItem),
+    /// Typing or pasting in a shared level, with why the last try didn't
+    /// read.
+    Paste(String, Option<String>),
+    Game(Box<Level>),
+    Complete(Summary, CompleteItem),
+    /// The turrets got every player, on the level with this index.
+    GameOver(usize),
+    /// Every level has been beaten.
+    Ending,
 }
 
 #[derive(Debug, EnumIter, PartialEq, Eq)]
 pub enum MenuItem {
     Play,
+    Levels,
+    Paste,
     Options,
     Quit,
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
-pub enum Cell {
-    Empty,
-    Player {
-        on_target: bool,
-        hp: u8,
-    },
-    Turret {
-        direction: Direction,
-        cooldown: u8,
-    },
-    Bullet {
-        direction: Direction,
-        on_target: bool,
-    },
-    Box {
-        locked: bool,
-    },
+#[derive(Debug, EnumIter, PartialEq, Eq)]
+pub enum CompleteItem {
+    Next,
+    Replay,
+    Solution,
+    Menu,
+}
+
+#[derive(Debug, EnumIter, PartialEq, Eq)]
+pub enum OptionItem {
+    Animations,
+    BulletTrails,
+    LockFlash,
+    Glyphs,
+    RepeatHint,
+    Mode,
+    Back,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Deserialize)]
+pub enum GlyphSet {
+    #[default]
+    Blocks,
+    Ascii,
+}
+
+/// Which way round levels are played.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Mode {
+    #[default]
+    Forward,
+    /// Start from the solved position and pull the boxes back to where the
+    /// level starts them.
+    Reverse,
+}
+
+/// The floor of a square, under whatever stands on it.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Terrain {
+    #[default]
+    Plain,
     Wall,
+    /// Where the boxes need to end up.
     Target,
+    /// Players and boxes slide across until something stops them.
+    Ice,
+    /// Can only be crossed the way the arrow points.
+    OneWay(Direction),
+    /// Swallows the first box pushed in, then is just floor. Players can't
+    /// cross until then.
+    Hole,
+    /// Stepping on one puts the player on the other with the same number.
+    Teleporter(u8),
+    /// Picked up by walking over it, and opens one door with the same id.
+    Key(u8),
+    Door(u8),
+    /// Every gate is open while every plate has a box or player on it.
+    Plate,
+    Gate {
+        open: bool,
+    },
+}
+
+/// A kind of special square or piece a level may use, beyond walls, boxes
+/// and targets.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, EnumIter, Serialize, Deserialize)]
+pub enum Mechanic {
+    Turrets,
+    Ice,
+    OneWay,
+    Holes,
+    Teleporters,
+    Keys,
+    Gates,
+}
+
+/// Whatever stands on a square, on top of its terrain.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum Occupant {
+    Empty,
+    Player { hp: u8 },
+    Turret { direction: Direction, cooldown: u8 },
+    Bullet { direction: Direction },
+    Box,
 }
@@ -0,0 +1,172 @@
+use crate::app::Level;
+use crate::enums::{Direction, Occupant, Terrain};
+use crate::grid::{Coord, Grid, Offset};
+use crate::topology::Topology;
+
+/// Makes a random level by starting from a solved room and pulling boxes
+/// around at random, so there is always a way back.
+pub fn generate(seed: u64, width: u16, height: u16, boxes: usize) -> Level {
+    let mut rng = Rng::new(seed);
+    let (width, height) = (width.max(4) as usize, height.max(4) as usize);
+    let mut room = Room {
+        width,
+        height,
+        floor: vec![false; width * height],
+        targets: vec![],
+        boxes: vec![],
+        player: 0,
+    };
+
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            room.floor[y * width + x] = true;
+        }
+    }
+    // Scatter pillars over up to a quarter of the room, keeping the floor in
+    // one piece.
+    for _ in 0..(width - 2) * (height - 2) / 4 {
+        let at = rng.below(width * height);
+        if room.floor[at] {
+            room.floor[at] = false;
+            if !room.is_connected() {
+                room.floor[at] = true;
+            }
+        }
+    }
+
+    let mut open: Vec<usize> = (0..width * height).filter(|&at| room.floor[at]).collect();
+    for _ in 0..boxes.min(open.len().saturating_sub(1)) {
+        let at = open.swap_remove(rng.below(open.len()));
+        room.targets.push(at);
+        room.boxes.push(at);
+    }
+    room.player = open[rng.below(open.len())];
+
+    // Keep going until something is out of place, within reason: a cramped
+    // room may not allow any pulls at all.
+    for _ in 0..10 {
+        for _ in 0..width * height * 20 {
+            let direction = Topology::Square.directions()[rng.below(4)];
+            room.pull(direction, rng.below(3) > 0);
+        }
+        if room.boxes.iter().any(|b| !room.targets.contains(b)) {
+            break;
+        }
+    }
+
+    room.into_level()
+}
+
+struct Room {
+    width: usize,
+    height: usize,
+    floor: Vec<bool>,
+    targets: Vec<usize>,
+    boxes: Vec<usize>,
+    player: usize,
+}
+
+impl Room {
+    fn step(&self, at: usize, direction: Direction) -> Option<usize> {
+        let Offset(dx, dy) = direction.into();
+        let x = (at % self.width).checked_add_signed(dx as isize)?;
+        let y = (at / self.width).checked_add_signed(dy as isize)?;
+        (x < self.width && y < self.height).then_some(y * self.width + x)
+    }
+
+    fn is_free(&self, at: usize) -> bool {
+        self.floor[at] && !self.boxes.contains(&at)
+    }
+
+    /// Walks the player one step, dragging along the box behind them if
+    /// `pulling`. This is a push played backwards.
+    fn pull(&mut self, direction: Direction, pulling: bool) {
+        let Some(to) = self.step(self.player, direction) else {
+            return;
+        };
+        if !self.is_free(to) {
+            return;
+        }
+        let behind = self.step(self.player, direction.opposite());
+        if let Some(b) = behind.filter(|_| pulling) {
+            if let Some(pulled) = self.boxes.iter_mut().find(|pulled| **pulled == b) {
+                *pulled = self.player;
+            }
+        }
+        self.player = to;
+    }
+
+    fn is_connected(&self) -> bool {
+        let Some(start) = self.floor.iter().position(|&f| f) else {
+            return true;
+        };
+        let mut seen = vec![false; self.floor.len()];
+        seen[start] = true;
+        let mut stack = vec![start];
+        while let Some(at) = stack.pop() {
+            for &direction in Topology::Square.directions() {
+                if let Some(next) = self.step(at, direction) {
+                    if self.floor[next] && !seen[next] {
+                        seen[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+        self.floor
+            .iter()
+            .zip(seen)
+            .all(|(&floor, seen)| !floor || seen)
+    }
+
+    fn into_level(self) -> Level {
+        let (width, height) = (self.width as u16, self.height as u16);
+        let index = |Coord(x, y): Coord| y as usize * self.width + x as usize;
+        let terrain = Grid::from_fn(width, height, |at| {
+            if !self.floor[index(at)] {
+                Terrain::Wall
+            } else if self.targets.contains(&index(at)) {
+                Terrain::Target
+            } else {
+                Terrain::Plain
+            }
+        });
+        let occupants = Grid::from_fn(width, height, |at| {
+            if self.boxes.contains(&index(at)) {
+                Occupant::Box
+            } else if index(at) == self.player {
+                Occupant::Player { hp: 3 }
+            } else {
+                Occupant::Empty
+            }
+        });
+        (terrain, occupants)
+            .try_into()
+            .expect("generated levels always place a player")
+    }
+}
+
+/// xorshift64*, plenty for shuffling a level and reproducible from a seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // One round of splitmix64, so nearby seeds start far apart.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        // Zero would get stuck at zero forever.
+        Self((z ^ (z >> 31)).max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+}
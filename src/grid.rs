@@ -0,0 +1,225 @@
+use std::ops::{Index, IndexMut, Mul};
+
+use serde::{Deserialize, Serialize};
+
+use crate::enums::Direction;
+use crate::topology::Topology;
+
+/// A position on a [`Grid`]: `Coord(x, y)`, counting columns from the left and
+/// rows from the top.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct Coord(pub u16, pub u16);
+
+#[derive(Clone, Copy)]
+pub struct Offset(pub i16, pub i16);
+
+/// A rectangle of cells stored row by row in one allocation, so copying or
+/// hashing a whole board is a single pass over a `Vec`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "Unchecked<T>")]
+pub struct Grid<T> {
+    width: u16,
+    height: u16,
+    #[serde(default)]
+    topology: Topology,
+    cells: Vec<T>,
+}
+
+/// A grid as read from a file, before checking it has as many cells as its
+/// size says.
+#[derive(Deserialize)]
+struct Unchecked<T> {
+    width: u16,
+    height: u16,
+    #[serde(default)]
+    topology: Topology,
+    cells: Vec<T>,
+}
+
+impl<T> TryFrom<Unchecked<T>> for Grid<T> {
+    type Error = String;
+
+    fn try_from(grid: Unchecked<T>) -> Result<Self, Self::Error> {
+        let size = grid.width as usize * grid.height as usize;
+        if grid.cells.len() != size {
+            return Err(format!(
+                "a {}x{} grid needs {size} cells, not {}",
+                grid.width,
+                grid.height,
+                grid.cells.len()
+            ));
+        }
+        Ok(Self {
+            width: grid.width,
+            height: grid.height,
+            topology: grid.topology,
+            cells: grid.cells,
+        })
+    }
+}
+
+impl<T> Grid<T> {
+    /// Builds a grid by asking `f` for the cell at each position.
+    pub fn from_fn(width: u16, height: u16, mut f: impl FnMut(Coord) -> T) -> Self {
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |x| Coord(x, y)))
+            .map(&mut f)
+            .collect();
+        Self {
+            width,
+            height,
+            topology: Topology::default(),
+            cells,
+        }
+    }
+
+    pub fn with_topology(self, topology: Topology) -> Self {
+        Self { topology, ..self }
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    fn index_of(&self, at: Coord) -> Option<usize> {
+        (at.0 < self.width && at.1 < self.height)
+            .then(|| at.1 as usize * self.width as usize + at.0 as usize)
+    }
+
+    pub fn get(&self, at: Coord) -> Option<&T> {
+        self.cells.get(self.index_of(at)?)
+    }
+
+    /// The next cell over from `at`, if that is still on the grid and the
+    /// topology has an edge that way.
+    pub fn neighbour(&self, at: Coord, direction: Direction) -> Option<Coord> {
+        let next = at.checked_add(self.topology.offset(at, direction)?)?;
+        self.index_of(next).map(|_| next)
+    }
+
+    /// Every cell next to `at` that is on the grid, with the way to get there.
+    pub fn neighbours(&self, at: Coord) -> impl Iterator<Item = (Direction, Coord)> + '_ {
+        self.topology
+            .directions()
+            .iter()
+            .filter_map(move |&direction| Some((direction, self.neighbour(at, direction)?)))
+    }
+
+    /// The rows from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.cells.chunks(self.width.max(1) as usize)
+    }
+
+    /// Every cell along with where it is, row by row. The unused columns of
+    /// hex boards are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = (Coord, &T)> {
+        let width = self.width.max(1) as usize;
+        let topology = self.topology;
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, cell)| (Coord((i % width) as u16, (i / width) as u16), cell))
+            .filter(move |&(at, _)| topology.is_cell(at))
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    /// Builds a grid from rows, padding short ones out with `fill`. Panics if
+    /// there are more rows or columns than fit in a `u16`.
+    pub fn from_rows(rows: Vec<Vec<T>>, fill: T) -> Self {
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let height = rows.len();
+        let mut cells = Vec::with_capacity(width * height);
+        for mut row in rows {
+            row.resize(width, fill.clone());
+            cells.extend(row);
+        }
+        Self {
+            width: width.try_into().expect("too many columns for a grid"),
+            height: height.try_into().expect("too many rows for a grid"),
+            topology: Topology::default(),
+            cells,
+        }
+    }
+
+    /// Surrounds the grid with a one cell border of `with`.
+    pub fn wrap(&mut self, with: T) {
+        *self = Grid::from_fn(self.width + 2, self.height + 2, |Coord(x, y)| {
+            x.checked_sub(1)
+                .zip(y.checked_sub(1))
+                .and_then(|(x, y)| self.get(Coord(x, y)))
+                .unwrap_or(&with)
+                .clone()
+        })
+        .with_topology(self.topology);
+    }
+}
+
+impl<T: PartialEq> Grid<T> {
+    pub fn count(&self, getting: T) -> usize {
+        self.cells.iter().filter(|&cell| *cell == getting).count()
+    }
+}
+
+impl<T> Index<Coord> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, index: Coord) -> &Self::Output {
+        match self.index_of(index) {
+            Some(i) => &self.cells[i],
+            None => panic!("{index:?} is off a {}x{} grid", self.width, self.height),
+        }
+    }
+}
+
+impl<T> IndexMut<Coord> for Grid<T> {
+    fn index_mut(&mut self, index: Coord) -> &mut Self::Output {
+        match self.index_of(index) {
+            Some(i) => &mut self.cells[i],
+            None => panic!("{index:?} is off a {}x{} grid", self.width, self.height),
+        }
+    }
+}
+
+impl Coord {
+    /// `None` if the result would fall off the top or left edge. Nothing here
+    /// knows about the right or bottom edges; use [`Grid::neighbour`] for that.
+    pub fn checked_add(self, rhs: Offset) -> Option<Self> {
+        Some(Self(
+            self.0.checked_add_signed(rhs.0)?,
+            self.1.checked_add_signed(rhs.1)?,
+        ))
+    }
+}
+
+impl Mul<i16> for Offset {
+    type Output = Self;
+
+    fn mul(self, rhs: i16) -> Self::Output {
+        Self(self.0.saturating_mul(rhs), self.1.saturating_mul(rhs))
+    }
+}
+
+impl From<Direction> for Offset {
+    fn from(value: Direction) -> Self {
+        use Direction::*;
+        match value {
+            Up => Self(0, -1),
+            Down => Self(0, 1),
+            Right => Self(1, 0),
+            Left => Self(-1, 0),
+            UpLeft => Self(-1, -1),
+            UpRight => Self(1, -1),
+            DownLeft => Self(-1, 1),
+            DownRight => Self(1, 1),
+        }
+    }
+}
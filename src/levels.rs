@@ -0,0 +1,655 @@
+use std::{fs, iter, mem, path::Path};
+
+use color_eyre::{
+    eyre::{bail, eyre, WrapErr},
+    Result,
+};
+
+use serde::Deserialize;
+
+use crate::app::{Level, Metadata};
+use crate::enums::{Direction, Mechanic, Occupant, Terrain};
+use crate::grid::Grid;
+use crate::topology::Topology;
+
+/// The levels that ship with the game.
+pub fn builtin() -> Vec<Level> {
+    parse_xsb(BUILTIN).expect("built-in levels are valid")
+}
+
+const BUILTIN: &str = "\
+#######
+#     #
+# @.$ #
+#     #
+#######
+Title: First push
+Par moves: 6
+Par pushes: 1
+
+  #####
+###   #
+#.@$  #
+### $.#
+#.##$ #
+# # . ##
+#$ *$$.#
+#   .  #
+########
+Title: Storeroom
+";
+
+/// The most rows or columns a board may have. Grids count in `u16`, with
+/// room for a margin, and nobody plays anything near this big anyway.
+pub const MAX_SIDE: usize = 1000;
+
+/// Reads every level in a collection file: JSON if it ends in `.json`, SLC if
+/// it ends in `.slc` or `.xml`, and XSB otherwise.
+pub fn load(path: &Path) -> Result<Vec<Level>> {
+    let text = read(path)?;
+    let levels = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => parse_json(&text),
+        Some("slc" | "xml") => parse_slc(&text),
+        _ => parse_xsb(&text),
+    }
+    .wrap_err_with(|| format!("{} isn't a valid level file", path.display()))?;
+    if levels.is_empty() {
+        bail!("no levels found in {}", path.display());
+    }
+    Ok(levels)
+}
+
+/// Reads the boards of a collection file like `load`, but only as far as
+/// their terrain and occupants, so boards that couldn't be played (with no
+/// player, say) can still be looked over. Boards from XSB and SLC files get
+/// the same margin as loaded levels.
+pub fn load_boards(path: &Path) -> Result<Vec<(Grid<Terrain>, Grid<Occupant>)>> {
+    /// Just the board of a JSON level.
+    #[derive(Deserialize)]
+    struct Bare {
+        terrain: Grid<Terrain>,
+        occupants: Grid<Occupant>,
+    }
+
+    let text = read(path)?;
+    let boards = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str::<Vec<Bare>>(&text)
+            .map(|levels| {
+                levels
+                    .into_iter()
+                    .map(|bare| (bare.terrain, bare.occupants))
+                    .collect()
+            })
+            .map_err(Into::into),
+        Some("slc" | "xml") => slc_boards(&text).and_then(wrapped),
+        _ => xsb_boards(&text).and_then(wrapped),
+    }
+    .wrap_err_with(|| format!("{} isn't a valid level file", path.display()))?;
+    if boards.is_empty() {
+        bail!("no levels found in {}", path.display());
+    }
+    Ok(boards)
+}
+
+fn read(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).wrap_err_with(|| format!("couldn't read {}", path.display()))?;
+    // Older collections are often Latin-1, which maps byte for byte to chars.
+    Ok(String::from_utf8(bytes)
+        .unwrap_or_else(|error| error.into_bytes().into_iter().map(char::from).collect()))
+}
+
+fn wrapped(boards: Vec<Board>) -> Result<Vec<(Grid<Terrain>, Grid<Occupant>)>> {
+    boards
+        .iter()
+        .enumerate()
+        .map(|(i, board)| {
+            let (mut terrain, mut occupants) = board.grids(i)?;
+            terrain.wrap(Terrain::Plain);
+            occupants.wrap(Occupant::Empty);
+            Ok((terrain, occupants))
+        })
+        .collect()
+}
+
+/// A level as written in an XSB or SLC file: its board rows and whatever the
+/// file says about it, not yet checked to be playable.
+struct Board {
+    rows: Vec<String>,
+    topology: Topology,
+    meta: Metadata,
+}
+
+/// Parses a JSON collection: an array of levels, each an object like
+///
+/// ```json
+/// {
+///   "meta": { "title": "First push", "par_moves": 6, "mechanics": [] },
+///   "terrain": {
+///     "width": 5, "height": 3, "topology": "Square",
+///     "cells": ["Wall", "Wall", "Wall", "Wall", "Wall",
+///               "Wall", "Plain", "Plain", "Target", "Wall",
+///               "Wall", "Wall", "Wall", "Wall", "Wall"]
+///   },
+///   "occupants": {
+///     "width": 5, "height": 3, "topology": "Square",
+///     "cells": ["Empty", "Empty", "Empty", "Empty", "Empty",
+///               "Empty", {"Player": {"hp": 3}}, "Box", "Empty", "Empty",
+///               "Empty", "Empty", "Empty", "Empty", "Empty"]
+///   }
+/// }
+/// ```
+///
+/// Grids list their cells row by row. `topology` is `Square`, `Hex` or
+/// `Triangle`, and defaults to `Square`.
+///
+/// Terrain cells are `Plain`, `Wall`, `Target`, `Ice`, `Hole`, `Plate`,
+/// `{"OneWay": "Up"}` (or any other direction), `{"Teleporter": 1}`,
+/// `{"Key": 0}`, `{"Door": 0}` and `{"Gate": {"open": false}}`. Occupant
+/// cells are `Empty`, `Box`, `{"Player": {"hp": 3}}`,
+/// `{"Turret": {"direction": "Left", "cooldown": 0}}` and
+/// `{"Bullet": {"direction": "Left"}}`.
+///
+/// `meta` may leave out any of `title`, `author`, `difficulty`, `par_moves`,
+/// `par_pushes`, `hint`, `mechanics` (such as `["Ice", "Turrets"]`) and
+/// `fire_rate`.
+///
+/// Saved games carry on with how far play had got, all optional: `mode`
+/// (`Forward` or `Reverse`), `move_counter`, `push_counter`, `history` in
+/// LURD, `time` as `{"secs": 12, "nanos": 0}`, `active` (which player is
+/// moving, from 0) and `players`, a list of
+/// `{"location": [x, y], "alive": true, "moves": 0, "pushes": 0, "keys": []}`
+/// in reading order. Undo history isn't saved.
+pub fn parse_json(text: &str) -> Result<Vec<Level>> {
+    let mut levels: Vec<Level> = serde_json::from_str(text)?;
+    for (i, level) in levels.iter_mut().enumerate() {
+        check_mechanics(level)
+            .wrap_err_with(|| format!("level {} doesn't match its metadata", i + 1))?;
+    }
+    Ok(levels)
+}
+
+/// Parses an SLC collection, the XML format many published packs use:
+///
+/// ```xml
+/// <SokobanLevels>
+///   <LevelCollection Copyright="Someone">
+///     <Level Id="First push" Width="5" Height="3">
+///       <L>#####</L>
+///       <L>#@$.#</L>
+///       <L>#####</L>
+///     </Level>
+///   </LevelCollection>
+/// </SokobanLevels>
+/// ```
+///
+/// Each level's `Id` becomes its title and its `Copyright`, or else the
+/// collection's, its author.
+pub fn parse_slc(text: &str) -> Result<Vec<Level>> {
+    levels_from(slc_boards(text)?)
+}
+
+fn slc_boards(text: &str) -> Result<Vec<Board>> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let document = roxmltree::Document::parse_with_options(text, options)?;
+    let mut boards = vec![];
+    for collection in document
+        .descendants()
+        .filter(|node| node.has_tag_name("LevelCollection"))
+    {
+        for node in collection
+            .children()
+            .filter(|node| node.has_tag_name("Level"))
+        {
+            let number = boards.len() + 1;
+            let rows: Vec<&str> = node
+                .children()
+                .filter(|row| row.has_tag_name("L"))
+                .map(|row| row.text().unwrap_or_default())
+                .collect();
+            if rows.is_empty() {
+                bail!("level {number} has no rows");
+            }
+            if let Some(row) = rows.iter().find(|row| !is_board_chars(row)) {
+                bail!("level {number} has a row that isn't a board row: {row:?}");
+            }
+            let size = |name| -> Result<Option<usize>> {
+                node.attribute(name)
+                    .map(|value| value.parse())
+                    .transpose()
+                    .wrap_err_with(|| format!("level {number} has a bad {name}"))
+            };
+            let width = rows
+                .iter()
+                .map(|row| row.chars().count())
+                .max()
+                .unwrap_or(0);
+            if size("Width")?.is_some_and(|declared| width > declared)
+                || size("Height")?.is_some_and(|declared| rows.len() > declared)
+            {
+                bail!("level {number} is bigger than its Width and Height say");
+            }
+            let meta = Metadata {
+                title: node.attribute("Id").map(str::to_owned),
+                author: node
+                    .attribute("Copyright")
+                    .or(collection.attribute("Copyright"))
+                    .map(str::to_owned),
+                ..Metadata::default()
+            };
+            boards.push(Board {
+                rows: rows.into_iter().map(str::to_owned).collect(),
+                topology: Topology::Square,
+                meta,
+            });
+        }
+    }
+    Ok(boards)
+}
+
+/// Writes levels as a JSON collection, as read by `parse_json`.
+pub fn to_json(levels: &[Level]) -> String {
+    serde_json::to_string_pretty(levels).expect("levels always serialise")
+}
+
+/// Parses the usual plain text Sokoban format, where each level is a block of
+/// board rows and anything else (titles, comments) separates them.
+///
+/// A `Topology: Hexoban` or `Topology: Trioban` line switches the levels after
+/// it to hex or triangle boards. Hexoban boards put a cell on every other
+/// column, with alternate rows shifted by one.
+///
+/// Other `Key: value` lines after a board describe that level; see
+/// `read_field` for the keys understood.
+pub fn parse_xsb(text: &str) -> Result<Vec<Level>> {
+    levels_from(xsb_boards(text)?)
+}
+
+fn xsb_boards(text: &str) -> Result<Vec<Board>> {
+    let mut boards: Vec<Board> = vec![];
+    let mut rows = vec![];
+    let mut topology = Topology::Square;
+    for line in text.lines().chain([""]) {
+        let line = line.trim_end();
+        if is_board_row(line) {
+            rows.push(line.to_owned());
+            continue;
+        }
+        if !rows.is_empty() {
+            boards.push(Board {
+                rows: mem::take(&mut rows),
+                topology,
+                meta: Metadata::default(),
+            });
+        }
+        if let Some(name) = line.strip_prefix("Topology:") {
+            let Some(named) = Topology::from_name(name.trim()) else {
+                bail!("unknown topology {:?}", name.trim());
+            };
+            topology = named;
+        } else if let (Some((key, value)), number @ 1..) = (line.split_once(':'), boards.len()) {
+            let board = &mut boards[number - 1];
+            read_field(&mut board.meta, key, value)
+                .wrap_err_with(|| format!("level {} has a bad {} line", number, key.trim()))?;
+        }
+    }
+    Ok(boards)
+}
+
+/// Turns boards read from a file into levels, checking each is playable and
+/// uses the mechanics it says it does.
+fn levels_from(boards: Vec<Board>) -> Result<Vec<Level>> {
+    let mut levels = vec![];
+    for (i, board) in boards.into_iter().enumerate() {
+        let mut level = board.level(i)?;
+        check_mechanics(&mut level)
+            .wrap_err_with(|| format!("level {} doesn't match its metadata", i + 1))?;
+        levels.push(level);
+    }
+    Ok(levels)
+}
+
+/// Reads one line of a level's metadata, such as `Title: Storeroom`. Unknown
+/// keys are ignored, since collections often carry extra ones like `Comment`.
+///
+/// | Key          | Value                                         |
+/// |--------------|-----------------------------------------------|
+/// | `Title`      | any text                                      |
+/// | `Author`     | any text                                      |
+/// | `Difficulty` | a number                                      |
+/// | `Par moves`  | a number, `Par` for short                     |
+/// | `Par pushes` | a number                                      |
+/// | `Hint`       | any text                                      |
+/// | `Mechanics`  | comma separated, such as `ice, keys, turrets` |
+/// | `Fire rate`  | ticks between turret shots                    |
+fn read_field(meta: &mut Metadata, key: &str, value: &str) -> Result<()> {
+    let value = value.trim();
+    match key.trim().to_ascii_lowercase().as_str() {
+        "title" => meta.title = Some(value.to_owned()),
+        "author" => meta.author = Some(value.to_owned()),
+        "difficulty" => meta.difficulty = Some(value.parse()?),
+        "par" | "par moves" => meta.par_moves = Some(value.parse()?),
+        "par pushes" => meta.par_pushes = Some(value.parse()?),
+        "hint" => meta.hint = Some(value.to_owned()),
+        "mechanics" => {
+            meta.mechanics = value
+                .split(',')
+                .map(|name| {
+                    Mechanic::from_name(name.trim())
+                        .ok_or_else(|| eyre!("unknown mechanic {:?}", name.trim()))
+                })
+                .collect::<Result<_>>()?;
+        }
+        "fire rate" => meta.fire_rate = value.parse()?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Fills in the mechanics a level uses if its file didn't list them, or
+/// checks it sticks to the ones it did.
+fn check_mechanics(level: &mut Level) -> Result<()> {
+    let used = level.mechanics();
+    if level.meta.mechanics.is_empty() {
+        level.meta.mechanics = used;
+    } else if let Some(unlisted) = used.iter().find(|m| !level.meta.mechanics.contains(m)) {
+        bail!(
+            "it uses {} without listing them under Mechanics",
+            unlisted.name()
+        );
+    }
+    Ok(())
+}
+
+fn is_board_row(line: &str) -> bool {
+    line.contains('#') && is_board_chars(line)
+}
+
+fn is_board_chars(line: &str) -> bool {
+    line.chars()
+        .all(|c| " -_#@+$*.~<>^vo123456789abcdeABCDE=!".contains(c))
+}
+
+/// The floor written with `c`: `#` for a wall, `.`, `+` or `*` for a
+/// target, `~` for ice, `<>^v` for one-way
+/// arrows, `o` for a hole, matching digits for a pair of teleporters, `a` to
+/// `e` for keys opening doors `A` to `E`, `=` for a pressure plate and `!`
+/// for a gate.
+fn terrain_from_char(c: char) -> Terrain {
+    match c {
+        '#' => Terrain::Wall,
+        '.' | '+' | '*' => Terrain::Target,
+        '~' => Terrain::Ice,
+        '<' => Terrain::OneWay(Direction::Left),
+        '>' => Terrain::OneWay(Direction::Right),
+        '^' => Terrain::OneWay(Direction::Up),
+        'v' => Terrain::OneWay(Direction::Down),
+        'o' => Terrain::Hole,
+        '1'..='9' => Terrain::Teleporter(c as u8 - b'0'),
+        'a'..='e' => Terrain::Key(c as u8 - b'a'),
+        'A'..='E' => Terrain::Door(c as u8 - b'A'),
+        '=' => Terrain::Plate,
+        '!' => Terrain::Gate { open: false },
+        _ => Terrain::Plain,
+    }
+}
+
+fn terrain_to_char(terrain: Terrain) -> char {
+    match terrain {
+        Terrain::Plain => ' ',
+        Terrain::Wall => '#',
+        Terrain::Target => '.',
+        Terrain::Ice => '~',
+        Terrain::OneWay(Direction::Left) => '<',
+        Terrain::OneWay(Direction::Right) => '>',
+        Terrain::OneWay(Direction::Up) => '^',
+        Terrain::OneWay(Direction::Down) => 'v',
+        // The diagonals of hex boards have no character of their own.
+        Terrain::OneWay(_) => ' ',
+        Terrain::Hole => 'o',
+        Terrain::Teleporter(pair) => char::from(b'0' + pair),
+        Terrain::Key(id) => char::from(b'a' + id),
+        Terrain::Door(id) => char::from(b'A' + id),
+        Terrain::Plate => '=',
+        Terrain::Gate { .. } => '!',
+    }
+}
+
+impl Board {
+    /// The level this board makes, the `index`th in its file.
+    fn level(self, index: usize) -> Result<Level> {
+        let (terrain, occupants) = self.grids(index)?;
+        let player = |(_, occupant): (_, &Occupant)| matches!(occupant, Occupant::Player { .. });
+        if !occupants.iter().any(player) {
+            bail!("level {} has no player", index + 1);
+        }
+        let mut level: Level = (terrain, occupants).try_into()?;
+        level.meta = self.meta;
+        Ok(level)
+    }
+
+    /// The terrain and occupants of the board, without the margin levels get.
+    fn grids(&self, index: usize) -> Result<(Grid<Terrain>, Grid<Occupant>)> {
+        let (rows, topology) = (&self.rows, self.topology);
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        if width > MAX_SIDE || rows.len() > MAX_SIDE {
+            bail!(
+                "level {} is bigger than {MAX_SIDE} squares across or down",
+                index + 1
+            );
+        }
+        let mut occupants = vec![];
+        let mut terrain = vec![];
+        // Hex cells go where `x + y` is even, so shift the board to suit.
+        let shift = match (topology, rows[0].find(|c| c != ' ')) {
+            (Topology::Hex, Some(first)) => first % 2,
+            _ => 0,
+        };
+        for (y, row) in rows.iter().enumerate() {
+            let row = " ".repeat(shift) + row;
+            let mut between = row.chars().enumerate().filter(|&(x, _)| (x + y) % 2 == 1);
+            if topology == Topology::Hex && between.any(|(_, c)| c != ' ') {
+                bail!("level {} isn't laid out as a Hexoban board", index + 1);
+            }
+            let row_occupants: Vec<Occupant> = row
+                .chars()
+                .map(|c| match c {
+                    '$' | '*' => Occupant::Box,
+                    '@' | '+' => Occupant::Player { hp: 3 },
+                    _ => Occupant::Empty,
+                })
+                .collect();
+            occupants.push(row_occupants);
+            terrain.push(row.chars().map(terrain_from_char).collect());
+        }
+        let terrain = Grid::from_rows(terrain, Terrain::Plain).with_topology(topology);
+        for pair in 1..=9 {
+            let count = terrain.count(Terrain::Teleporter(pair));
+            if count != 0 && count != 2 {
+                bail!(
+                    "level {} has {count} teleporters numbered {pair}, expected two",
+                    index + 1
+                );
+            }
+        }
+        let occupants = Grid::from_rows(occupants, Occupant::Empty).with_topology(topology);
+        Ok((terrain, occupants))
+    }
+}
+
+/// Writes levels as an XSB collection. Turrets and bullets have no XSB
+/// equivalent, so they come out as walls and floor. Special floor under a
+/// player or box is lost.
+pub fn to_xsb(levels: &[Level]) -> String {
+    let mut out = String::new();
+    let mut topology = Topology::Square;
+    for (i, level) in levels.iter().enumerate() {
+        out.push_str(&format!("; {}\n\n", i + 1));
+        // The topology carries over to later levels until changed.
+        if level.occupants.topology() != topology {
+            topology = level.occupants.topology();
+            out.push_str(&format!("Topology: {}\n\n", topology.name()));
+        }
+        for row in trimmed_rows(level) {
+            out.push_str(&row);
+            out.push('\n');
+        }
+        for (key, value) in fields(&level.meta) {
+            out.push_str(&format!("{key}: {value}\n"));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Writes a level as one line of run-length encoded XSB, short enough to
+/// paste into a chat message: rows are split by `|`, floor is `-`, and a
+/// count goes before a repeated character, as in `4#|#@$.#|4#`. Only the
+/// board is kept, not the metadata.
+pub fn to_rle(level: &Level) -> Result<String> {
+    if level.occupants.topology() != Topology::Square {
+        bail!("only square boards can be written as RLE");
+    }
+    if level
+        .terrain
+        .iter()
+        .any(|(_, terrain)| matches!(terrain, Terrain::Teleporter(_)))
+    {
+        bail!("teleporters can't be written as RLE, their digits would read as counts");
+    }
+    let rows: Vec<String> = trimmed_rows(level)
+        .iter()
+        .map(|row| {
+            let mut out = String::new();
+            let mut chars = row
+                .chars()
+                .map(|c| if c == ' ' { '-' } else { c })
+                .peekable();
+            while let Some(c) = chars.next() {
+                let mut count = 1;
+                while chars.next_if_eq(&c).is_some() {
+                    count += 1;
+                }
+                if count > 1 {
+                    out.push_str(&count.to_string());
+                }
+                out.push(c);
+            }
+            out
+        })
+        .collect();
+    Ok(rows.join("|"))
+}
+
+/// Reads a level written by `to_rle`. Rows may also be split by newlines, so
+/// a plain XSB board reads too.
+pub fn from_rle(text: &str) -> Result<Level> {
+    let mut rows = vec![];
+    for (i, line) in text.trim().split(['|', '\n']).enumerate() {
+        let mut row = String::new();
+        let mut count = String::new();
+        for c in line.trim_end_matches('\r').chars() {
+            if c.is_ascii_digit() {
+                count.push(c);
+                continue;
+            }
+            let c = if c == '-' || c == '_' { ' ' } else { c };
+            let times: usize = if count.is_empty() { 1 } else { count.parse()? };
+            if times == 0 {
+                bail!("row {} repeats {c:?} zero times", i + 1);
+            }
+            if row.len() + times > MAX_SIDE {
+                bail!("row {} is wider than {MAX_SIDE} squares", i + 1);
+            }
+            row.extend(iter::repeat_n(c, times));
+            count.clear();
+        }
+        if !count.is_empty() {
+            bail!("row {} ends in a count with nothing to repeat", i + 1);
+        }
+        if !is_board_chars(&row) {
+            bail!("row {} isn't a board row: {line:?}", i + 1);
+        }
+        if rows.len() == MAX_SIDE {
+            bail!("the level has more than {MAX_SIDE} rows");
+        }
+        rows.push(row);
+    }
+    let board = Board {
+        rows,
+        topology: Topology::Square,
+        meta: Metadata::default(),
+    };
+    let mut level = board.level(0)?;
+    check_mechanics(&mut level)?;
+    Ok(level)
+}
+
+/// The metadata lines written after a level's board.
+fn fields(meta: &Metadata) -> Vec<(&'static str, String)> {
+    let mut fields = vec![];
+    let mut add = |key, value: Option<String>| {
+        if let Some(value) = value {
+            fields.push((key, value));
+        }
+    };
+    add("Title", meta.title.clone());
+    add("Author", meta.author.clone());
+    add("Difficulty", meta.difficulty.map(|n| n.to_string()));
+    add("Par moves", meta.par_moves.map(|n| n.to_string()));
+    add("Par pushes", meta.par_pushes.map(|n| n.to_string()));
+    add("Hint", meta.hint.clone());
+    let names: Vec<_> = meta.mechanics.iter().map(|m| m.name()).collect();
+    add("Mechanics", (!names.is_empty()).then(|| names.join(", ")));
+    let default = Metadata::default().fire_rate;
+    add(
+        "Fire rate",
+        (meta.fire_rate != default).then(|| meta.fire_rate.to_string()),
+    );
+    fields
+}
+
+/// The board as XSB rows, without the empty margin levels are padded with.
+fn trimmed_rows(level: &Level) -> Vec<String> {
+    let rows: Vec<String> = level
+        .occupants
+        .rows()
+        .zip(level.terrain.rows())
+        .map(|(row, terrain)| {
+            row.iter()
+                .zip(terrain)
+                .map(|(occupant, &terrain)| {
+                    let target = terrain == Terrain::Target;
+                    match occupant {
+                        Occupant::Turret { .. } => '#',
+                        Occupant::Box if target => '*',
+                        Occupant::Box => '$',
+                        Occupant::Player { .. } if target => '+',
+                        Occupant::Player { .. } => '@',
+                        Occupant::Empty | Occupant::Bullet { .. } => terrain_to_char(terrain),
+                    }
+                })
+                .collect::<String>()
+                .trim_end()
+                .to_owned()
+        })
+        .collect();
+    let first = rows.iter().position(|row| !row.is_empty()).unwrap_or(0);
+    let last = rows.iter().rposition(|row| !row.is_empty()).unwrap_or(0);
+    let rows = &rows[first..=last];
+    let margin = rows
+        .iter()
+        .filter(|row| !row.is_empty())
+        .map(|row| row.len() - row.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    rows.iter()
+        .map(|row| row.get(margin..).unwrap_or_default().to_owned())
+        .collect()
+}
@@ -1,18 +1,164 @@
-#![allow(unused)]
-
+mod anim;
 mod app;
+mod cli;
+mod config;
 mod enums;
+mod generate;
+mod grid;
+mod levels;
+mod path;
+mod position;
+mod records;
+mod solver;
+mod topology;
 mod ui;
+mod validate;
+
+use std::{
+    io, panic,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use std::io;
+use clap::Parser;
+use color_eyre::{config::HookBuilder, Result};
+use ratatui::crossterm::{
+    event::{
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture,
+    },
+    execute,
+};
 
 use crate::app::App;
+use crate::cli::{Cli, Command};
+use crate::config::Config;
+use crate::enums::Mode;
+
+/// Whether `play` has the terminal in raw mode on the alternate screen, and so
+/// needs it put back.
+static TERMINAL_TAKEN: AtomicBool = AtomicBool::new(false);
+
+fn main() -> Result<()> {
+    install_hooks()?;
+    let cli = Cli::parse();
+    let config = match &cli.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
 
-fn main() -> io::Result<()> {
+    match cli.command {
+        None => play(App {
+            config,
+            ..App::default()
+        }),
+        Some(Command::Play {
+            file,
+            level,
+            reverse,
+        }) => {
+            let mut app = App {
+                config,
+                ..App::default()
+            };
+            if reverse {
+                app.config.mode = Mode::Reverse;
+            }
+            match file {
+                Some(file) if file.as_os_str() == "-" => {
+                    let text = io::read_to_string(io::stdin())?;
+                    app.load_shared(&text)?;
+                }
+                Some(file) => {
+                    app.levels = levels::load(&file)?;
+                    app.collection = file.display().to_string();
+                }
+                None => {}
+            }
+            cli::pick(&app.levels, level)?;
+            app.start_level(level - 1);
+            play(app)
+        }
+        Some(Command::Solve {
+            file,
+            level,
+            max_nodes,
+        }) => cli::solve(&file, level, max_nodes),
+        Some(Command::Bench {
+            file,
+            max_nodes,
+            seconds,
+            json,
+        }) => cli::bench(&file, max_nodes, seconds, json.as_deref()),
+        Some(Command::Verify {
+            file,
+            solution,
+            level,
+        }) => cli::verify(&file, &solution, level),
+        Some(Command::Generate {
+            seed,
+            width,
+            height,
+            boxes,
+        }) => {
+            let seed = seed.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |time| time.as_secs())
+            });
+            cli::print_generated(seed, width, height, boxes);
+            Ok(())
+        }
+        Some(Command::Convert {
+            input,
+            output,
+            normalise,
+        }) => cli::convert(&input, output.as_deref(), normalise),
+        Some(Command::Check { file }) => cli::check(&file),
+    }
+}
+
+fn play(mut app: App) -> Result<()> {
     let mut terminal = ratatui::init();
+    TERMINAL_TAKEN.store(true, Ordering::SeqCst);
+    execute!(
+        io::stdout(),
+        EnableMouseCapture,
+        EnableFocusChange,
+        EnableBracketedPaste
+    )?;
     terminal.clear()?;
-    let mut app = App::default();
     let app_result = app.run(&mut terminal);
-    ratatui::restore();
+    restore_terminal();
     app_result
 }
+
+/// Installs color-eyre's reports. A panic can strike mid-game, so that hook
+/// puts the terminal back to normal before printing anything; errors only
+/// reach `main` after `play` has already done so.
+fn install_hooks() -> Result<()> {
+    let (panic_hook, eyre_hook) = HookBuilder::default().into_hooks();
+    eyre_hook.install()?;
+
+    let panic_hook = panic_hook.into_panic_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        panic_hook(info);
+    }));
+    Ok(())
+}
+
+/// Does nothing unless `play` set the terminal up, so other commands' output
+/// isn't mixed with escape codes.
+fn restore_terminal() {
+    if !TERMINAL_TAKEN.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    let _ = execute!(
+        io::stdout(),
+        DisableMouseCapture,
+        DisableFocusChange,
+        DisableBracketedPaste
+    );
+    ratatui::restore();
+}
@@ -0,0 +1,193 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+use strum::IntoEnumIterator;
+
+use crate::app::Level;
+use crate::enums::{Direction, Occupant, Terrain};
+use crate::grid::Coord;
+
+/// Shortest sequence of moves walking the player from `from` to `to` without
+/// pushing anything. Ice, one-way arrows, holes and teleporters work as they
+/// do in play. Doors and gates count as they are now, so a walk that relies
+/// on a key picked up or a plate let go of on the way may stop short.
+pub fn walk_path(level: &Level, from: Coord, to: Coord) -> Option<Vec<Direction>> {
+    let free = |at: Coord| at == from || level.occupants[at] == Occupant::Empty;
+    search(
+        from,
+        |pos| pos == to,
+        |pos, direction| walk_step(level, pos, direction, &free),
+    )
+}
+
+/// Like `walk_path`, but timed so the player never walks into a bullet,
+/// going by where the bullets will be on each tick of the walk. The player
+/// makes one move a tick.
+pub fn safe_walk_path(level: &Level, from: Coord, to: Coord) -> Option<Vec<Direction>> {
+    let armed = level
+        .occupants
+        .iter()
+        .any(|(_, occupant)| matches!(occupant, Occupant::Turret { .. } | Occupant::Bullet { .. }));
+    if !armed {
+        return walk_path(level, from, to);
+    }
+    // Going round every square is as long as a sensible walk gets.
+    let ticks = level.occupants.width() as usize * level.occupants.height() as usize;
+    let danger = bullet_danger(level, ticks);
+    // Bullets in the way now will have moved on, so only the timing rules
+    // those squares out.
+    let free = |at: Coord| {
+        at == from
+            || matches!(
+                level.occupants[at],
+                Occupant::Empty | Occupant::Bullet { .. }
+            )
+    };
+    search(
+        (from, 0),
+        |(pos, _)| pos == to,
+        |(pos, tick), direction| {
+            let next = walk_step(level, pos, direction, &free)?;
+            (!danger.get(tick)?.contains(&next)).then_some((next, tick + 1))
+        },
+    )
+}
+
+/// For each tick from now, the squares a player moving on that tick must keep
+/// off: those with a bullet in as the tick starts, and those a bullet moves
+/// or is fired into.
+fn bullet_danger(level: &Level, ticks: usize) -> Vec<HashSet<Coord>> {
+    let mut ahead = level.clone();
+    // The player is walking off, so won't be there to stop any bullets.
+    ahead.occupants[level.player_location()] = Occupant::Empty;
+    let bullets = |level: &Level| -> HashSet<Coord> {
+        level
+            .occupants
+            .iter()
+            .filter(|(_, occupant)| matches!(occupant, Occupant::Bullet { .. }))
+            .map(|(at, _)| at)
+            .collect()
+    };
+    (0..ticks)
+        .map(|_| {
+            let before = bullets(&ahead);
+            ahead.tick();
+            &before | &bullets(&ahead)
+        })
+        .collect()
+}
+
+/// Shortest sequence of moves, walking and pushing, that takes the box at
+/// `from` to `to`. Every other box is treated as an obstacle, and the box is
+/// never pushed into a hole.
+pub fn push_path(level: &Level, player: Coord, from: Coord, to: Coord) -> Option<Vec<Direction>> {
+    // The player and the box we are moving leave their cells, so those count
+    // as floor once the search is underway.
+    let empty =
+        |pos: Coord| pos == from || pos == player || level.occupants[pos] == Occupant::Empty;
+    search(
+        (player, from),
+        |(_, pos)| pos == to,
+        |(player, pushing), direction| {
+            let next = level.occupants.neighbour(player, direction)?;
+            if next != pushing {
+                let free = |pos: Coord| pos != pushing && empty(pos);
+                return walk_step(level, player, direction, &free).map(|to| (to, pushing));
+            }
+            if !level.can_enter(next, direction, true) {
+                return None;
+            }
+            let beyond = level.occupants.neighbour(pushing, direction)?;
+            let free = |pos: Coord| pos != pushing && empty(pos);
+            if !free(beyond) || !level.can_enter(beyond, direction, false) {
+                return None;
+            }
+            let landed = slide(level, beyond, direction, false, &free);
+            if level.terrain[landed] == Terrain::Hole {
+                return None;
+            }
+            let free = |pos: Coord| pos != landed && empty(pos);
+            let stopped = slide(level, next, direction, true, &free);
+            Some((teleport(level, stopped, &free), landed))
+        },
+    )
+}
+
+/// Where the player comes to rest stepping from `from` without pushing
+/// anything, after any ice and teleporter, going by which squares are `free`.
+fn walk_step(
+    level: &Level,
+    from: Coord,
+    direction: Direction,
+    free: &impl Fn(Coord) -> bool,
+) -> Option<Coord> {
+    let next = level.occupants.neighbour(from, direction)?;
+    if !free(next) || !level.can_enter(next, direction, true) {
+        return None;
+    }
+    let stopped = slide(level, next, direction, true, free);
+    Some(teleport(level, stopped, free))
+}
+
+/// How far something that just moved onto `at` slides across ice.
+fn slide(
+    level: &Level,
+    mut at: Coord,
+    direction: Direction,
+    player: bool,
+    free: &impl Fn(Coord) -> bool,
+) -> Coord {
+    while level.terrain[at] == Terrain::Ice {
+        match level.occupants.neighbour(at, direction) {
+            Some(next) if free(next) && level.can_enter(next, direction, player) => at = next,
+            _ => break,
+        }
+    }
+    at
+}
+
+/// Where a player stopping on `at` comes out, if it is a teleporter with a
+/// free partner.
+fn teleport(level: &Level, at: Coord, free: &impl Fn(Coord) -> bool) -> Coord {
+    let Terrain::Teleporter(pair) = level.terrain[at] else {
+        return at;
+    };
+    level
+        .terrain
+        .iter()
+        .find(|&(other, &terrain)| other != at && terrain == Terrain::Teleporter(pair))
+        .map(|(other, _)| other)
+        .filter(|&other| free(other))
+        .unwrap_or(at)
+}
+
+fn search<S: Copy + Eq + Hash>(
+    start: S,
+    done: impl Fn(S) -> bool,
+    next: impl Fn(S, Direction) -> Option<S>,
+) -> Option<Vec<Direction>> {
+    let mut came_from: HashMap<S, (S, Direction)> = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    while let Some(state) = queue.pop_front() {
+        if done(state) {
+            let mut moves = vec![];
+            let mut at = state;
+            while let Some(&(prev, direction)) = came_from.get(&at) {
+                moves.push(direction);
+                at = prev;
+            }
+            moves.reverse();
+            return Some(moves);
+        }
+        for direction in Direction::iter() {
+            let Some(to) = next(state, direction) else {
+                continue;
+            };
+            if to != start && !came_from.contains_key(&to) {
+                came_from.insert(to, (state, direction));
+                queue.push_back(to);
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,84 @@
+use crate::enums::Occupant;
+use crate::grid::{Coord, Grid};
+
+/// One bit per square of a grid, in row order.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct BitSet(Vec<u64>);
+
+impl BitSet {
+    pub fn insert(&mut self, index: usize) {
+        let (word, bit) = (index / 64, index % 64);
+        if word >= self.0.len() {
+            self.0.resize(word + 1, 0);
+        }
+        self.0[word] |= 1 << bit;
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = BitSet::default();
+        for index in iter {
+            set.insert(index);
+        }
+        set
+    }
+}
+
+/// A position reduced to what matters for solving it: which squares hold
+/// boxes, and the first square of the region the player can walk around in,
+/// both as indices in row order. Positions where the player stands in
+/// different spots of the same region are equal.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Position {
+    pub boxes: BitSet,
+    pub region: usize,
+}
+
+impl Position {
+    /// The position with boxes on `boxes`, where the player can walk to
+    /// every square set in `reach`.
+    pub fn new(boxes: &[usize], reach: &[bool]) -> Self {
+        Self {
+            boxes: boxes.iter().copied().collect(),
+            region: reach.iter().position(|&reached| reached).unwrap_or(0),
+        }
+    }
+}
+
+/// The Zobrist key for a box on `at`. XOR together the keys of everything on
+/// the board to hash a position; moving something is then two XORs.
+pub fn box_key(at: Coord) -> u64 {
+    key(at, 0)
+}
+
+/// The Zobrist key for the player standing on `at`.
+pub fn player_key(at: Coord) -> u64 {
+    key(at, 1)
+}
+
+/// The Zobrist key for the floor at `at` having changed from how the level
+/// started, by a key being picked up or a door opened.
+pub fn terrain_key(at: Coord) -> u64 {
+    key(at, 2)
+}
+
+/// The Zobrist hash of every box and player on the grid.
+pub fn zobrist(grid: &Grid<Occupant>) -> u64 {
+    grid.iter()
+        .map(|(at, cell)| match cell {
+            Occupant::Box => box_key(at),
+            Occupant::Player { .. } => player_key(at),
+            _ => 0,
+        })
+        .fold(0, |hash, key| hash ^ key)
+}
+
+/// splitmix64 of the square and piece, so keys need no table and work for any
+/// size of grid. The piece gets the low two bits to itself.
+fn key(Coord(x, y): Coord, piece: u64) -> u64 {
+    let mut z = ((x as u64) << 34 | (y as u64) << 2 | piece).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,60 @@
+use std::{collections::BTreeMap, env, fs, io, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// The best result for one level. Each field is tracked on its own, so the
+/// fewest moves and the fastest time may come from different attempts.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Record {
+    pub moves: usize,
+    pub pushes: usize,
+    pub time: Duration,
+}
+
+/// Personal bests kept between sessions, keyed by collection and level number
+/// like `builtin#0`.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Records(pub BTreeMap<String, Record>);
+
+impl Records {
+    /// Reads the saved records, starting afresh if there are none.
+    pub fn load() -> Self {
+        records_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = records_path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// Folds a finished attempt into the bests, returning what they were
+    /// before so the two can be compared.
+    pub fn submit(&mut self, level: String, attempt: Record) -> Option<Record> {
+        let previous = self.0.get(&level).copied();
+        let best = match previous {
+            Some(best) => Record {
+                moves: best.moves.min(attempt.moves),
+                pushes: best.pushes.min(attempt.pushes),
+                time: best.time.min(attempt.time),
+            },
+            None => attempt,
+        };
+        self.0.insert(level, best);
+        previous
+    }
+}
+
+fn records_path() -> Option<PathBuf> {
+    let data = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(data.join("sokoban").join("records.json"))
+}
@@ -0,0 +1,355 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet, VecDeque},
+    mem::size_of,
+    time::{Duration, Instant},
+};
+
+use crate::app::Level;
+use crate::enums::{Direction, Occupant, Terrain};
+use crate::grid::{Coord, Offset};
+use crate::position::Position;
+use crate::topology::Topology;
+
+pub enum Outcome {
+    /// A solution in LURD notation.
+    Solved(String),
+    /// Every reachable position was tried without success.
+    Unsolvable,
+    /// The node or time budget ran out first.
+    GaveUp,
+    /// The level uses something this solver doesn't handle.
+    Unsupported(&'static str),
+}
+
+/// How much work a search did.
+#[derive(Default)]
+pub struct Stats {
+    /// Positions taken off the queue and expanded.
+    pub expanded: usize,
+    /// Roughly how many bytes the search held at its largest.
+    pub memory: usize,
+}
+
+/// Searches for a solution with A* over box pushes, expanding at most
+/// `max_nodes` positions. Solutions use few pushes, not necessarily few moves.
+pub fn solve(level: &Level, max_nodes: usize) -> Outcome {
+    solve_within(level, max_nodes, None).0
+}
+
+/// Like `solve`, but also gives up once `time` has passed, and says how much
+/// work it did.
+pub fn solve_within(level: &Level, max_nodes: usize, time: Option<Duration>) -> (Outcome, Stats) {
+    let mut stats = Stats::default();
+    let deadline = time.map(|time| Instant::now() + time);
+    let outcome = search(level, max_nodes, deadline, &mut stats);
+    (outcome, stats)
+}
+
+fn search(
+    level: &Level,
+    max_nodes: usize,
+    deadline: Option<Instant>,
+    stats: &mut Stats,
+) -> Outcome {
+    if level.players.len() > 1 {
+        return Outcome::Unsupported("levels with several players");
+    }
+    if level.occupants.topology() != Topology::Square {
+        return Outcome::Unsupported("hex and triangle boards");
+    }
+    let special =
+        |terrain: &Terrain| !matches!(terrain, Terrain::Plain | Terrain::Wall | Terrain::Target);
+    if level.terrain.iter().any(|(_, terrain)| special(terrain)) {
+        return Outcome::Unsupported("special floor like ice, doors or teleporters");
+    }
+    let board = Board::new(level);
+    let start = Node {
+        boxes: board.start_boxes.clone(),
+        player: board.start_player,
+        parent: usize::MAX,
+        push: None,
+        pushes: 0,
+    };
+    if start.boxes.iter().any(|&b| board.distance[b].is_none()) {
+        return Outcome::Unsolvable;
+    }
+
+    let mut nodes = vec![start];
+    let mut open = BinaryHeap::from([Reverse((board.estimate(&nodes[0].boxes), 0))]);
+    let mut closed = HashSet::new();
+    // Checking the clock is slow next to expanding a node, so it's only done
+    // every so often.
+    let late = || deadline.is_some_and(|deadline| Instant::now() > deadline);
+    let outcome = loop {
+        let Some(Reverse((_, id))) = open.pop() else {
+            break Outcome::Unsolvable;
+        };
+        let node = &nodes[id];
+        if node.boxes.iter().all(|&b| board.targets[b]) {
+            break Outcome::Solved(board.replay(&nodes, id));
+        }
+
+        let mut occupied = board.occupancy(&node.boxes);
+        let reach = board.reachable(node.player, &occupied);
+        if !closed.insert(Position::new(&node.boxes, &reach)) {
+            continue;
+        }
+        stats.expanded = closed.len();
+        if closed.len() > max_nodes || (closed.len().is_multiple_of(1024) && late()) {
+            break Outcome::GaveUp;
+        }
+
+        let mut children = vec![];
+        for (i, &b) in node.boxes.iter().enumerate() {
+            for &direction in Topology::Square.directions() {
+                let (Some(behind), Some(dest)) = (
+                    board.step(b, direction.opposite()),
+                    board.step(b, direction),
+                ) else {
+                    continue;
+                };
+                if !reach[behind] || !board.is_free(dest, &occupied) {
+                    continue;
+                }
+                if board.distance[dest].is_none() {
+                    continue;
+                }
+                occupied[b] = false;
+                occupied[dest] = true;
+                let frozen = board.is_frozen(dest, &occupied);
+                occupied[b] = true;
+                occupied[dest] = false;
+                if frozen {
+                    continue;
+                }
+                let mut boxes = node.boxes.clone();
+                boxes[i] = dest;
+                boxes.sort_unstable();
+                children.push(Node {
+                    boxes,
+                    player: b,
+                    parent: id,
+                    push: Some((b, direction)),
+                    pushes: node.pushes + 1,
+                });
+            }
+        }
+        for child in children {
+            let estimate = child.pushes + board.estimate(&child.boxes);
+            open.push(Reverse((estimate, nodes.len())));
+            nodes.push(child);
+        }
+    };
+
+    // Nothing is freed during the search, so its size at the end is its peak.
+    let words = board.floor.len().div_ceil(64);
+    stats.memory = nodes.capacity() * size_of::<Node>()
+        + nodes
+            .iter()
+            .map(|node| node.boxes.capacity() * size_of::<usize>())
+            .sum::<usize>()
+        + open.capacity() * size_of::<Reverse<(usize, usize)>>()
+        + closed.capacity() * (size_of::<Position>() + words * size_of::<u64>());
+    outcome
+}
+
+struct Node {
+    /// Box squares, sorted so equal positions compare equal.
+    boxes: Vec<usize>,
+    player: usize,
+    parent: usize,
+    /// The push that led here: the square the box left, and which way.
+    push: Option<(usize, Direction)>,
+    pushes: usize,
+}
+
+/// The fixed parts of a level, flattened so squares are plain indices.
+struct Board {
+    width: usize,
+    height: usize,
+    floor: Vec<bool>,
+    targets: Vec<bool>,
+    /// Fewest pushes from each square onto some target ignoring other boxes,
+    /// or `None` where a box could never reach one.
+    distance: Vec<Option<usize>>,
+    start_boxes: Vec<usize>,
+    start_player: usize,
+}
+
+impl Board {
+    fn new(level: &Level) -> Self {
+        let grid = &level.occupants;
+        let terrain = &level.terrain;
+        let (width, height) = (grid.width() as usize, grid.height() as usize);
+        let mut board = Board {
+            width,
+            height,
+            floor: vec![false; width * height],
+            targets: vec![false; width * height],
+            distance: vec![None; width * height],
+            start_boxes: vec![],
+            start_player: 0,
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let at = y * width + x;
+                let square = Coord(x as u16, y as u16);
+                let occupant = grid[square];
+                board.floor[at] = terrain[square] != Terrain::Wall
+                    && !matches!(occupant, Occupant::Turret { .. });
+                board.targets[at] = terrain[square] == Terrain::Target;
+                match occupant {
+                    Occupant::Box => board.start_boxes.push(at),
+                    Occupant::Player { .. } => board.start_player = at,
+                    _ => {}
+                }
+            }
+        }
+        board.compute_distances();
+        board
+    }
+
+    /// Pulls boxes backwards off every target to find how far each square is
+    /// from being solved.
+    fn compute_distances(&mut self) {
+        let mut queue: VecDeque<usize> = (0..self.floor.len())
+            .filter(|&at| self.targets[at] && self.floor[at])
+            .collect();
+        for &at in &queue {
+            self.distance[at] = Some(0);
+        }
+        while let Some(at) = queue.pop_front() {
+            let here = self.distance[at].unwrap_or(0);
+            for &direction in Topology::Square.directions() {
+                let Some(from) = self.step(at, direction) else {
+                    continue;
+                };
+                let Some(player) = self.step(from, direction) else {
+                    continue;
+                };
+                if self.floor[from] && self.floor[player] && self.distance[from].is_none() {
+                    self.distance[from] = Some(here + 1);
+                    queue.push_back(from);
+                }
+            }
+        }
+    }
+
+    fn step(&self, at: usize, direction: Direction) -> Option<usize> {
+        let Offset(dx, dy) = direction.into();
+        let x = (at % self.width).checked_add_signed(dx as isize)?;
+        let y = (at / self.width).checked_add_signed(dy as isize)?;
+        (x < self.width && y < self.height).then_some(y * self.width + x)
+    }
+
+    fn estimate(&self, boxes: &[usize]) -> usize {
+        boxes.iter().filter_map(|&b| self.distance[b]).sum()
+    }
+
+    fn occupancy(&self, boxes: &[usize]) -> Vec<bool> {
+        let mut occupied = vec![false; self.floor.len()];
+        for &b in boxes {
+            occupied[b] = true;
+        }
+        occupied
+    }
+
+    fn is_free(&self, at: usize, occupied: &[bool]) -> bool {
+        self.floor[at] && !occupied[at]
+    }
+
+    /// Whether the box at `at` is jammed into a 2x2 block of walls and boxes
+    /// that can never move again, with some box in it off target.
+    fn is_frozen(&self, at: usize, occupied: &[bool]) -> bool {
+        use Direction::*;
+        for (vertical, horizontal) in [(Up, Left), (Up, Right), (Down, Left), (Down, Right)] {
+            let (Some(a), Some(b)) = (self.step(at, vertical), self.step(at, horizontal)) else {
+                continue;
+            };
+            let Some(c) = self.step(a, horizontal) else {
+                continue;
+            };
+            let block = [at, a, b, c];
+            if block.iter().all(|&s| !self.floor[s] || occupied[s])
+                && block.iter().any(|&s| occupied[s] && !self.targets[s])
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn reachable(&self, from: usize, occupied: &[bool]) -> Vec<bool> {
+        let mut seen = vec![false; self.floor.len()];
+        seen[from] = true;
+        let mut queue = VecDeque::from([from]);
+        while let Some(at) = queue.pop_front() {
+            for &direction in Topology::Square.directions() {
+                if let Some(next) = self.step(at, direction) {
+                    if !seen[next] && self.is_free(next, occupied) {
+                        seen[next] = true;
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        seen
+    }
+
+    /// Shortest walk between two squares without pushing anything.
+    fn walk(&self, from: usize, to: usize, occupied: &[bool]) -> Vec<Direction> {
+        let mut came_from = vec![None; self.floor.len()];
+        let mut queue = VecDeque::from([from]);
+        while let Some(at) = queue.pop_front() {
+            if at == to {
+                break;
+            }
+            for &direction in Topology::Square.directions() {
+                if let Some(next) = self.step(at, direction) {
+                    if next != from && came_from[next].is_none() && self.is_free(next, occupied) {
+                        came_from[next] = Some((at, direction));
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        let mut moves = vec![];
+        let mut at = to;
+        while let Some((prev, direction)) = came_from[at] {
+            moves.push(direction);
+            at = prev;
+        }
+        moves.reverse();
+        moves
+    }
+
+    /// Turns the chain of pushes ending at `id` into the full LURD solution,
+    /// walking the player between pushes.
+    fn replay(&self, nodes: &[Node], id: usize) -> String {
+        let mut pushes = vec![];
+        let mut at = id;
+        while let Some(push) = nodes[at].push {
+            pushes.push(push);
+            at = nodes[at].parent;
+        }
+        pushes.reverse();
+
+        let mut occupied = self.occupancy(&self.start_boxes);
+        let mut player = self.start_player;
+        let mut solution = String::new();
+        for (b, direction) in pushes {
+            let behind = self.step(b, direction.opposite()).unwrap_or(b);
+            for step in self.walk(player, behind, &occupied) {
+                solution.push(step.lurd(false));
+            }
+            solution.push(direction.lurd(true));
+            occupied[b] = false;
+            if let Some(dest) = self.step(b, direction) {
+                occupied[dest] = true;
+            }
+            player = b;
+        }
+        solution
+    }
+}
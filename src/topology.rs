@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use crate::enums::Direction;
+use crate::grid::{Coord, Offset};
+
+/// The shape of a board's cells, which decides where each direction leads.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum Topology {
+    #[default]
+    Square,
+    /// Hexoban. Cells sit on every other column with alternate rows shifted
+    /// by one, just as the text format lays them out, so a cell's left and
+    /// right neighbours are two columns away.
+    Hex,
+    /// Trioban. Triangles where `x + y` is even point up and share an edge
+    /// with the triangle below, the rest point down and share one with the
+    /// triangle above. Boxes only move across an edge, so a box can only be
+    /// pushed up or down when the triangle beyond it has an edge that way.
+    Triangle,
+}
+
+impl Topology {
+    /// Every direction a cell might have a neighbour in.
+    pub fn directions(self) -> &'static [Direction] {
+        use Direction::*;
+        match self {
+            Topology::Square | Topology::Triangle => &[Up, Down, Left, Right],
+            Topology::Hex => &[UpLeft, UpRight, Left, Right, DownLeft, DownRight],
+        }
+    }
+
+    /// How far one step in `direction` from `at` goes, or `None` if the cell
+    /// has no edge that way.
+    pub fn offset(self, at: Coord, direction: Direction) -> Option<Offset> {
+        use Direction::*;
+        let points_up = (at.0 + at.1).is_multiple_of(2);
+        match (self, direction) {
+            (Topology::Square, Up | Down | Left | Right) => Some(direction.into()),
+            (Topology::Hex, Left | Right) => Some(Offset::from(direction) * 2),
+            (Topology::Hex, UpLeft | UpRight | DownLeft | DownRight) => Some(direction.into()),
+            (Topology::Triangle, Left | Right) => Some(direction.into()),
+            (Topology::Triangle, Down) if points_up => Some(direction.into()),
+            (Topology::Triangle, Up) if !points_up => Some(direction.into()),
+            _ => None,
+        }
+    }
+
+    /// Whether `at` is a cell at all. Hex boards leave every other column
+    /// empty.
+    pub fn is_cell(self, at: Coord) -> bool {
+        self != Topology::Hex || (at.0 + at.1).is_multiple_of(2)
+    }
+
+    /// The terminal column, from the left of the board, that the cell in
+    /// column `x` is drawn at. Every cell is two columns wide.
+    pub fn column(self, x: u16) -> u16 {
+        match self {
+            Topology::Hex => x,
+            _ => x * 2,
+        }
+    }
+
+    /// How many terminal columns a board `width` cells across takes up.
+    pub fn columns(self, width: u16) -> u16 {
+        match self {
+            Topology::Hex => width + 1,
+            _ => width * 2,
+        }
+    }
+
+    /// The cell drawn at terminal `column` (from the left of the board) on
+    /// row `y`.
+    pub fn cell_at(self, column: u16, y: u16) -> Option<Coord> {
+        let x = match self {
+            Topology::Hex if (column + y) % 2 == 1 => column.checked_sub(1)?,
+            Topology::Hex => column,
+            _ => column / 2,
+        };
+        Some(Coord(x, y))
+    }
+
+    /// What the `Topology:` line of a level file calls this.
+    pub fn name(self) -> &'static str {
+        match self {
+            Topology::Square => "Sokoban",
+            Topology::Hex => "Hexoban",
+            Topology::Triangle => "Trioban",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Topology::Square, Topology::Hex, Topology::Triangle]
+            .into_iter()
+            .find(|topology| topology.name().eq_ignore_ascii_case(name))
+    }
+}
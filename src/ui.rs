@@ -1,13 +1,18 @@
+use std::collections::{HashSet, VecDeque};
 use std::fmt;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use crate::anim::Tween;
 use crate::app::*;
 use crate::enums::*;
+use crate::grid::Coord;
+use crate::records::Record;
+use crate::topology::Topology;
 use ratatui::{
-    layout::Alignment,
+    layout::{Alignment, Position, Rect},
     style::{Color, Modifier, Style},
-    text::{Line, Span, Text},
-    widgets::{block::Title, Block, BorderType, Padding, Paragraph, Wrap},
+    text::{Line, Span},
+    widgets::{block::Title, Block, BorderType, Clear, Padding, Paragraph, Wrap},
     Frame,
 };
 use strum::IntoEnumIterator;
@@ -15,33 +20,7 @@ use strum::IntoEnumIterator;
 impl App {
     pub fn draw(&self, frame: &mut Frame) {
         use CurrentScreen::*;
-        let block = Block::bordered()
-            .border_type(BorderType::Rounded)
-            .title(Title::from(Span::styled(
-                match &self.current_screen {
-                    Menu(_) => "Sokoban!".to_owned(),
-                    Game(level) => format!(
-                        "Level{}: {} Moves: {} Boxes Remaining ",
-                        " 1",
-                        level.move_counter.to_string(),
-                        level.remaining_boxes
-                    ),
-                },
-                Style::default().fg(Color::Green),
-            )))
-            .padding(Padding::uniform(1))
-            .title_alignment(Alignment::Left)
-            .title_bottom(format!(
-                "FPS:{:.0}",
-                1.0 / self
-                    .timing_buffer
-                    .into_iter()
-                    .reduce(std::ops::Add::add)
-                    .unwrap()
-                    .div_f64(30.0)
-                    .as_secs_f64()
-            ))
-            .title_bottom("do some keybinds");
+        let block = self.border_block();
 
         let focused_style = Style::default().add_modifier(Modifier::BOLD);
         let unfocused_style = Style::default();
@@ -68,64 +47,978 @@ impl App {
 
                 frame.render_widget(menu_block, frame.area());
             }
-            Game(level) => {
-                let lines: Vec<_> = level
-                    .level_state
-                    .0
+            LevelSelect(selected) => {
+                let lines: Vec<_> = self
+                    .levels
                     .iter()
-                    .map(|row| {
-                        Line::from(row.iter().map(|cell| cell.to_span()).collect::<Vec<_>>())
+                    .enumerate()
+                    .map(|(i, level)| {
+                        let style = if i == *selected {
+                            focused_style
+                        } else {
+                            unfocused_style
+                        };
+                        let title = level.meta.title.as_deref().unwrap_or("Untitled");
+                        let mut details = level.meta.details();
+                        let mechanics: Vec<_> =
+                            level.meta.mechanics.iter().map(|m| m.name()).collect();
+                        if !mechanics.is_empty() {
+                            details.push(mechanics.join(", "));
+                        }
+                        Line::from(vec![
+                            Span::styled(format!("{:>3}. {title}", i + 1), style),
+                            Span::styled(
+                                format!("  {}", details.join(", ")),
+                                Style::default().fg(Color::DarkGray),
+                            ),
+                        ])
+                    })
+                    .collect();
+
+                // Keep the highlighted level in view on long collections.
+                let rows = block.inner(frame.area()).height as usize;
+                let scroll = (selected + 1).saturating_sub(rows) as u16;
+                let levels_block = Paragraph::new(lines).block(block).scroll((scroll, 0));
+
+                frame.render_widget(levels_block, frame.area());
+            }
+            Options(focused_item) => {
+                let lines: Vec<_> = OptionItem::iter()
+                    .map(|item| {
+                        Line::styled(
+                            self.option_label(&item),
+                            if focused_item == &item {
+                                focused_style
+                            } else {
+                                unfocused_style
+                            },
+                        )
                     })
                     .collect();
 
-                let game_block = Paragraph::new(lines).block(block).centered();
+                let options_block = Paragraph::new(lines)
+                    .block(block)
+                    .centered()
+                    .wrap(Wrap { trim: false });
+
+                frame.render_widget(options_block, frame.area());
+            }
+            Paste(text, error) => {
+                let mut lines = vec![
+                    Line::from("Paste or type a level, with rows split by | or new lines."),
+                    Line::styled(
+                        "Enter to play it, Esc to go back.",
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Line::default(),
+                ];
+                lines.extend(
+                    text.lines()
+                        .map(|line| Line::styled(line.to_owned(), focused_style)),
+                );
+                if let Some(error) = error {
+                    lines.push(Line::default());
+                    lines.push(Line::styled(error.clone(), Style::default().fg(Color::Red)));
+                }
+
+                let paste_block = Paragraph::new(lines)
+                    .block(block)
+                    .wrap(Wrap { trim: false });
+
+                frame.render_widget(paste_block, frame.area());
+            }
+            Complete(summary, focused_item) => {
+                let mut lines = summary.lines();
+                lines.push(Line::default());
+                lines.extend(CompleteItem::iter().map(|item| {
+                    Line::styled(
+                        complete_label(&item),
+                        if focused_item == &item {
+                            focused_style
+                        } else {
+                            unfocused_style
+                        },
+                    )
+                }));
+                if summary.show_solution {
+                    lines.push(Line::default());
+                    lines.push(Line::from(summary.solution.clone()));
+                }
+
+                let complete_block = Paragraph::new(lines)
+                    .block(block)
+                    .centered()
+                    .wrap(Wrap { trim: false });
+
+                frame.render_widget(complete_block, frame.area());
+            }
+            Ending => {
+                let lines = vec![
+                    Line::styled("All levels complete!", focused_style),
+                    Line::default(),
+                    Line::from("Thanks for playing."),
+                ];
+
+                let ending_block = Paragraph::new(lines)
+                    .block(block)
+                    .centered()
+                    .wrap(Wrap { trim: false });
+
+                frame.render_widget(ending_block, frame.area());
+            }
+            GameOver(index) => {
+                let lines = vec![
+                    Line::styled("Game over", Style::default().fg(Color::Red)),
+                    Line::default(),
+                    Line::from(format!("The turrets got you on level {}.", index + 1)),
+                    Line::default(),
+                    Line::styled(
+                        format!("{} to try again, Esc for the menu.", self.select_key()),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ];
+
+                let game_over_block = Paragraph::new(lines)
+                    .block(block)
+                    .centered()
+                    .wrap(Wrap { trim: false });
+
+                frame.render_widget(game_over_block, frame.area());
+            }
+            Game(level) => {
+                let topology = level.occupants.topology();
+                let inside = level.inside();
+                let mut lines = vec![Line::default(); level.occupants.height() as usize];
+                for (at, &occupant) in level.occupants.iter() {
+                    let line = &mut lines[at.1 as usize];
+                    // Hex rows are shifted half a cell from the ones above.
+                    let indent = topology.column(at.0) as usize - line.width();
+                    if indent > 0 {
+                        line.push_span(" ".repeat(indent));
+                    }
+                    let terrain = level.terrain[at];
+                    line.push_span(match (occupant, terrain) {
+                        // Empty triangles show which way they point.
+                        (Occupant::Empty, Terrain::Plain)
+                            if topology == Topology::Triangle && inside.contains(&at) =>
+                        {
+                            let glyph = if (at.0 + at.1).is_multiple_of(2) {
+                                "/\\"
+                            } else {
+                                "\\/"
+                            };
+                            Span::styled(glyph, Style::default().fg(Color::DarkGray))
+                        }
+                        _ => self.square_span(at, occupant, terrain),
+                    });
+                }
+
+                let grid_area = level.grid_area(block.inner(frame.area()));
+                frame.render_widget(block, frame.area());
+                frame.render_widget(Paragraph::new(lines), grid_area);
+
+                let now = Instant::now();
+                for tween in &self.animations.tweens {
+                    let occupant = level.occupants[tween.to];
+                    if occupant.is_sliding() {
+                        let on_target = level.terrain[tween.to] == Terrain::Target;
+                        let glyph = occupant.glyph(on_target, self.config.glyphs);
+                        draw_tween(frame, grid_area, topology, tween, glyph, now);
+                    }
+                }
+            }
+        }
+
+        if self.paused {
+            self.draw_paused(frame);
+        }
+        if let Some(share) = &self.share {
+            self.draw_share(frame, share);
+        }
+        if self.show_help {
+            self.draw_help(frame);
+        }
+    }
+
+    fn draw_share(&self, frame: &mut Frame, share: &str) {
+        let area = frame.area();
+        let width = (share.len() as u16 + 4).clamp(40, 80).min(area.width);
+        let text_width = width.saturating_sub(4).max(1) as usize;
+        let height = (share.len().div_ceil(text_width) as u16 + 4).min(area.height);
+        let popup = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        let share = Paragraph::new(vec![
+            Line::from(share.to_owned()),
+            Line::default(),
+            Line::styled("Any key to close", Style::default().fg(Color::DarkGray)),
+        ])
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title("Share")
+                .padding(Padding::horizontal(1)),
+        );
+        frame.render_widget(Clear, popup);
+        frame.render_widget(share, popup);
+    }
+
+    fn draw_paused(&self, frame: &mut Frame) {
+        let area = frame.area();
+        frame
+            .buffer_mut()
+            .set_style(area, Style::default().add_modifier(Modifier::DIM));
+
+        let hint = match self.config.keymap.keys_for(KeyBind::Pause).first() {
+            Some(key) => format!("{key} to resume"),
+            None => String::new(),
+        };
+        let width = (hint.len() as u16 + 4).max(12).min(area.width);
+        let height = 4.min(area.height);
+        let popup = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        let paused = Paragraph::new(vec![
+            Line::styled("Paused", Style::default().add_modifier(Modifier::BOLD)),
+            Line::from(hint),
+        ])
+        .centered()
+        .block(Block::bordered().border_type(BorderType::Rounded));
+        frame.render_widget(Clear, popup);
+        frame.render_widget(paused, popup);
+    }
+
+    fn draw_help(&self, frame: &mut Frame) {
+        let heading = Style::default().add_modifier(Modifier::BOLD);
+        let keymap = &self.config.keymap;
+
+        let mut actions: Vec<KeyBind> = vec![];
+        for &(_, action) in &keymap.0 {
+            if !actions.contains(&action) {
+                actions.push(action);
+            }
+        }
+
+        let mut lines = vec![Line::styled("Controls", heading)];
+        for action in actions {
+            let keys: Vec<_> = keymap
+                .keys_for(action)
+                .iter()
+                .map(|key| key.to_string())
+                .collect();
+            lines.push(Line::from(format!(
+                "{:<16}{}",
+                keys.join(", "),
+                action.description()
+            )));
+        }
+        lines.push(Line::from(format!(
+            "{:<16}{}",
+            "Mouse", "Walk, or pick a box to push"
+        )));
+
+        // In a game, only explain the terrain the level has.
+        let shown = |mechanic: Option<Mechanic>| match (&self.current_screen, mechanic) {
+            (CurrentScreen::Game(level), Some(mechanic)) => {
+                level.meta.mechanics.contains(&mechanic)
+            }
+            _ => true,
+        };
+
+        lines.push(Line::default());
+        lines.push(Line::styled("Legend", heading));
+        for (squares, description) in LEGEND {
+            let mut spans: Vec<_> = squares
+                .iter()
+                .map(|&(occupant, terrain)| compose(occupant, terrain, self.config.glyphs))
+                .collect();
+            spans
+                .push(format!("{:width$}{description}", "", width = 10 - squares.len() * 2).into());
+            lines.push(Line::from(spans));
+        }
+        for (terrain, description) in TERRAIN_LEGEND {
+            if !shown(terrain[0].mechanic()) {
+                continue;
+            }
+            let mut spans: Vec<_> = terrain
+                .iter()
+                .map(|terrain| terrain.to_span(self.config.glyphs))
+                .collect();
+            spans
+                .push(format!("{:width$}{description}", "", width = 10 - terrain.len() * 2).into());
+            lines.push(Line::from(spans));
+        }
+        let hint = match &self.current_screen {
+            CurrentScreen::Game(level) => level.meta.hint.as_ref(),
+            _ => None,
+        };
+        if let Some(hint) = hint {
+            lines.push(Line::default());
+            lines.push(Line::styled("Hint", heading));
+            lines.push(Line::from(hint.clone()));
+        }
+
+        let area = frame.area();
+        let width = 50.min(area.width);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let popup = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        let help = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title("Help")
+                .padding(Padding::horizontal(1)),
+        );
+        frame.render_widget(Clear, popup);
+        frame.render_widget(help, popup);
+    }
 
-                frame.render_widget(game_block, frame.area());
+    fn square_span(&self, at: Coord, occupant: Occupant, terrain: Terrain) -> Span<'static> {
+        if occupant.is_sliding() && self.animations.tween_to(at).is_some() {
+            // Drawn on top afterwards, partway between cells.
+            return terrain.to_span(self.config.glyphs);
+        }
+        if let Some(ghost) = self.ghost.as_ref().filter(|ghost| ghost.at == at) {
+            // Red once a push there has failed, until it moves again.
+            let color = if ghost.stuck {
+                Color::Red
+            } else {
+                Color::Green
+            };
+            let on_target = terrain == Terrain::Target;
+            let glyph = Occupant::Box.glyph(on_target, self.config.glyphs);
+            return Span::styled(glyph, Style::default().fg(color));
+        }
+        let span = compose(occupant, terrain, self.config.glyphs);
+        if self.cursor == Some(at) {
+            return span.patch_style(Style::default().bg(Color::Blue));
+        }
+        let waiting = match &self.current_screen {
+            CurrentScreen::Game(level) => {
+                matches!(occupant, Occupant::Player { .. }) && level.player_location() != at
+            }
+            _ => false,
+        };
+        if self.selected_box == Some(at) {
+            span.style(Style::default().fg(Color::Yellow))
+        } else if self.animations.is_flashing(at) {
+            span.style(Style::default().add_modifier(Modifier::REVERSED))
+        } else if waiting {
+            // Players waiting their turn fade into the background.
+            span.style(Style::default().fg(Color::DarkGray))
+        } else if (occupant, terrain) == (Occupant::Empty, Terrain::Plain)
+            && self.animations.has_trail(at)
+        {
+            Span::styled("··", Style::default().fg(Color::DarkGray))
+        } else {
+            span
+        }
+    }
+
+    fn option_label(&self, item: &OptionItem) -> String {
+        let on_off = |on| if on { "On" } else { "Off" };
+        match item {
+            OptionItem::Animations => format!("Animations: {}", on_off(self.config.animations)),
+            OptionItem::BulletTrails => {
+                format!("Bullet trails: {}", on_off(self.config.bullet_trails))
+            }
+            OptionItem::LockFlash => format!("Lock flash: {}", on_off(self.config.lock_flash)),
+            OptionItem::Glyphs => format!("Glyphs: {:?}", self.config.glyphs),
+            OptionItem::RepeatHint => {
+                format!(
+                    "Repeated position hint: {}",
+                    on_off(self.config.repeat_hint)
+                )
             }
+            OptionItem::Mode => format!("Mode: {:?}", self.config.mode),
+            OptionItem::Back => "Back".to_owned(),
         }
     }
+
+    /// The grid cell drawn at terminal position (`column`, `row`), if any.
+    pub fn coord_at(&self, area: Rect, column: u16, row: u16) -> Option<Coord> {
+        let CurrentScreen::Game(level) = &self.current_screen else {
+            return None;
+        };
+        let grid_area = level.grid_area(self.border_block().inner(area));
+        if !grid_area.contains(Position::new(column, row)) {
+            return None;
+        }
+        let topology = level.occupants.topology();
+        let at = topology.cell_at(column - grid_area.x, row - grid_area.y)?;
+        level.occupants.get(at).is_some().then_some(at)
+    }
+
+    /// The first key bound to select, for hints.
+    fn select_key(&self) -> String {
+        self.config
+            .keymap
+            .keys_for(KeyBind::Select)
+            .first()
+            .map_or("select".to_owned(), |key| key.to_string())
+    }
+
+    fn border_block(&self) -> Block<'static> {
+        use CurrentScreen::*;
+        Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title(Title::from(Span::styled(
+                match &self.current_screen {
+                    Menu(_) => "Sokoban!".to_owned(),
+                    LevelSelect(_) => "Levels".to_owned(),
+                    Options(_) => "Options".to_owned(),
+                    Paste(..) => "Paste a level".to_owned(),
+                    Game(level) => format!(
+                        "Level {}{}: {} Moves: {} Boxes Remaining {}{}{}",
+                        level.index + 1,
+                        match &level.meta.title {
+                            Some(title) => format!(" \"{title}\""),
+                            None => String::new(),
+                        },
+                        level.move_counter,
+                        level.remaining_boxes,
+                        match level.players.len() {
+                            1 => String::new(),
+                            count => format!(
+                                "Player {}/{count}: {} Moves ",
+                                level.active + 1,
+                                level.players[level.active].moves
+                            ),
+                        },
+                        match (level.mode, level.pulling) {
+                            (Mode::Forward, _) => "",
+                            (Mode::Reverse, false) => "(reverse) ",
+                            (Mode::Reverse, true) => "(reverse, pulling) ",
+                        },
+                        match &level.players[level.active].keys[..] {
+                            [] => String::new(),
+                            keys => format!(
+                                "Keys: {} ",
+                                keys.iter()
+                                    .map(|&id| char::from(b'a' + id))
+                                    .collect::<String>()
+                            ),
+                        }
+                    ),
+                    Complete(summary, _) => format!("Level {} complete!", summary.level + 1),
+                    Ending => "Sokoban!".to_owned(),
+                    GameOver(index) => format!("Level {} lost", index + 1),
+                },
+                Style::default().fg(Color::Green),
+            )))
+            .padding(Padding::uniform(1))
+            .title_alignment(Alignment::Left)
+            .title(match &self.current_screen {
+                Game(_) if self.cursor.is_some() => Title::from(Span::styled(
+                    format!(" Pick a square, {} to walk there ", self.select_key()),
+                    Style::default().fg(Color::Yellow),
+                ))
+                .alignment(Alignment::Right),
+                Game(_) if self.ghost.is_some() => Title::from(Span::styled(
+                    format!(" Steer the box, {} to push it there ", self.select_key()),
+                    Style::default().fg(Color::Yellow),
+                ))
+                .alignment(Alignment::Right),
+                Game(level) if level.repeated && self.config.repeat_hint => Title::from(
+                    Span::styled(" Repeated position ", Style::default().fg(Color::Yellow)),
+                )
+                .alignment(Alignment::Right),
+                _ => Title::default(),
+            })
+            .title_bottom(format!(
+                "FPS:{:.0}",
+                1.0 / self
+                    .timing_buffer
+                    .into_iter()
+                    .reduce(std::ops::Add::add)
+                    .unwrap()
+                    .div_f64(30.0)
+                    .as_secs_f64()
+            ))
+            .title_bottom(match self.config.keymap.keys_for(KeyBind::Help).first() {
+                Some(key) => format!("{key}: help"),
+                None => String::new(),
+            })
+            .title_bottom(match &self.current_screen {
+                Game(level) => Line::from(level.meta.details().join(", ")).right_aligned(),
+                _ => Line::default(),
+            })
+    }
 }
 
-impl Cell {
-    fn to_string(&self) -> &str {
-        use Cell::*;
-        match self {
-            Player {
-                on_target: false,
-                hp,
-            } => "@@",
-            Player {
-                on_target: true,
-                hp,
-            } => "@<",
-            Box { locked: false } => "[]",
-            Empty => "  ",
-            Wall => "██",
-            Target => "><",
-            Box { locked: true } => "░░",
-            Turret {
-                direction,
-                cooldown: _,
-            } => match direction {
-                Direction::Up => "▟▙",
-                Direction::Left => "┫█",
-                Direction::Right => "█┣",
-                Direction::Down => "▜▛",
+impl Summary {
+    fn lines(&self) -> Vec<Line<'static>> {
+        let best = |field: fn(&Record) -> String| match &self.best {
+            Some(best) => format!("best {}", field(best)),
+            None => "first clear".to_owned(),
+        };
+        let par = |par: Option<usize>| match par {
+            Some(par) => format!(", par {par}"),
+            None => String::new(),
+        };
+        let mut lines = vec![
+            Line::from(format!(
+                "Moves: {} ({}{})",
+                self.attempt.moves,
+                best(|record| record.moves.to_string()),
+                par(self.par_moves)
+            )),
+            Line::from(format!(
+                "Pushes: {} ({}{})",
+                self.attempt.pushes,
+                best(|record| record.pushes.to_string()),
+                par(self.par_pushes)
+            )),
+            Line::from(format!(
+                "Time: {} ({})",
+                format_time(self.attempt.time),
+                best(|record| format_time(record.time))
+            )),
+            Line::from(format!("HP left: {}", self.hp)),
+        ];
+        if self
+            .best
+            .is_some_and(|best| self.attempt.moves < best.moves || self.attempt.time < best.time)
+        {
+            lines.push(Line::styled(
+                "New personal best!",
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        lines
+    }
+}
+
+impl Metadata {
+    /// Who made the level, how hard it is and its par, as far as known.
+    fn details(&self) -> Vec<String> {
+        let mut details = vec![];
+        if let Some(author) = &self.author {
+            details.push(format!("by {author}"));
+        }
+        if let Some(difficulty) = self.difficulty {
+            details.push(format!("difficulty {difficulty}"));
+        }
+        match (self.par_moves, self.par_pushes) {
+            (Some(moves), Some(pushes)) => {
+                details.push(format!("par {moves} moves, {pushes} pushes"));
+            }
+            (Some(moves), None) => details.push(format!("par {moves} moves")),
+            (None, Some(pushes)) => details.push(format!("par {pushes} pushes")),
+            (None, None) => {}
+        }
+        details
+    }
+}
+
+fn complete_label(item: &CompleteItem) -> &'static str {
+    match item {
+        CompleteItem::Next => "Next level",
+        CompleteItem::Replay => "Replay",
+        CompleteItem::Solution => "View solution",
+        CompleteItem::Menu => "Main menu",
+    }
+}
+
+fn format_time(time: Duration) -> String {
+    let secs = time.as_secs_f64();
+    format!("{}:{:04.1}", (secs / 60.0) as u64, secs % 60.0)
+}
+
+impl Level {
+    /// Every square the players could reach if the boxes weren't there.
+    fn inside(&self) -> HashSet<Coord> {
+        let grid = &self.terrain;
+        let mut inside: HashSet<Coord> = self.players.iter().map(|p| p.location).collect();
+        let mut queue: VecDeque<Coord> = inside.iter().copied().collect();
+        while let Some(at) = queue.pop_front() {
+            for (_, next) in grid.neighbours(at) {
+                if grid[next] != Terrain::Wall && inside.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        inside
+    }
+
+    /// Where the grid is drawn inside `area`, centred horizontally with each
+    /// cell two columns wide.
+    fn grid_area(&self, area: Rect) -> Rect {
+        let width = self
+            .occupants
+            .topology()
+            .columns(self.occupants.width())
+            .min(area.width);
+        let height = self.occupants.height().min(area.height);
+        Rect::new(
+            area.x + (area.width / 2).saturating_sub(width / 2),
+            area.y,
+            width,
+            height,
+        )
+    }
+}
+
+/// The floor explained on the help screen.
+const TERRAIN_LEGEND: [(&[Terrain], &str); 10] = [
+    (&[Terrain::Target], "Target"),
+    (&[Terrain::Wall], "Wall"),
+    (&[Terrain::Plain], "Floor"),
+    (&[Terrain::Ice], "Ice, slide until stopped"),
+    (
+        &[
+            Terrain::OneWay(Direction::Up),
+            Terrain::OneWay(Direction::Down),
+            Terrain::OneWay(Direction::Left),
+            Terrain::OneWay(Direction::Right),
+        ],
+        "One way only",
+    ),
+    (&[Terrain::Hole], "Hole, fill it with a box"),
+    (&[Terrain::Teleporter(1)], "Teleporter to its pair"),
+    (
+        &[Terrain::Key(0), Terrain::Door(0)],
+        "Key and the door it opens",
+    ),
+    (&[Terrain::Plate], "Plate, holds gates open"),
+    (
+        &[Terrain::Gate { open: false }, Terrain::Gate { open: true }],
+        "Gate, shut and open",
+    ),
+];
+
+/// Everything that stands on the floor explained on the help screen.
+const LEGEND: [(&[(Occupant, Terrain)], &str); 6] = [
+    (&[(Occupant::Player { hp: 3 }, Terrain::Plain)], "You"),
+    (
+        &[(Occupant::Player { hp: 3 }, Terrain::Target)],
+        "You, on a target",
+    ),
+    (&[(Occupant::Box, Terrain::Plain)], "Box"),
+    (&[(Occupant::Box, Terrain::Target)], "Box on a target"),
+    (
+        &[
+            (
+                Occupant::Turret {
+                    direction: Direction::Up,
+                    cooldown: 0,
+                },
+                Terrain::Plain,
+            ),
+            (
+                Occupant::Turret {
+                    direction: Direction::Down,
+                    cooldown: 0,
+                },
+                Terrain::Plain,
+            ),
+            (
+                Occupant::Turret {
+                    direction: Direction::Left,
+                    cooldown: 0,
+                },
+                Terrain::Plain,
+            ),
+            (
+                Occupant::Turret {
+                    direction: Direction::Right,
+                    cooldown: 0,
+                },
+                Terrain::Plain,
+            ),
+        ],
+        "Turret, fires the way it faces",
+    ),
+    (
+        &[(
+            Occupant::Bullet {
+                direction: Direction::Right,
+            },
+            Terrain::Plain,
+        )],
+        "Bullet, costs a hit point",
+    ),
+];
+
+/// A square drawn as whatever stands on it, or its floor if nothing does.
+fn compose(occupant: Occupant, terrain: Terrain, glyphs: GlyphSet) -> Span<'static> {
+    match occupant {
+        Occupant::Empty => terrain.to_span(glyphs),
+        _ => occupant.glyph(terrain == Terrain::Target, glyphs).into(),
+    }
+}
+
+/// Draws `glyph` partway along its slide. Horizontal moves step a column at a
+/// time; vertical ones can only jump a whole row, so they switch halfway.
+fn draw_tween(
+    frame: &mut Frame,
+    grid_area: Rect,
+    topology: Topology,
+    tween: &Tween,
+    glyph: &str,
+    now: Instant,
+) {
+    let t = tween.progress(now);
+    let from_x = f64::from(topology.column(tween.from.0));
+    let to_x = f64::from(topology.column(tween.to.0));
+    let x = grid_area.x + (from_x + (to_x - from_x) * t).round() as u16;
+    let y = grid_area.y + if t < 0.5 { tween.from.1 } else { tween.to.1 };
+    if !grid_area.contains(Position::new(x, y)) {
+        return;
+    }
+    let max_width = (grid_area.right() - x) as usize;
+    frame
+        .buffer_mut()
+        .set_stringn(x, y, glyph, max_width, Style::default());
+}
+
+impl Terrain {
+    fn to_span(self, glyphs: GlyphSet) -> Span<'static> {
+        use Direction::*;
+        let arrow = |blocks, ascii| match glyphs {
+            GlyphSet::Blocks => blocks,
+            GlyphSet::Ascii => ascii,
+        };
+        let text = match self {
+            Terrain::Plain => "  ".to_owned(),
+            Terrain::Wall => arrow("██", "##").to_owned(),
+            Terrain::Target => arrow("><", "..").to_owned(),
+            Terrain::Ice => "~~".to_owned(),
+            Terrain::OneWay(direction) => match direction {
+                Up => arrow("↑↑", "^^"),
+                Down => arrow("↓↓", "vv"),
+                Left => arrow("←←", "<<"),
+                Right => arrow("→→", ">>"),
+                UpLeft => arrow("↖↖", "\\\\"),
+                UpRight => arrow("↗↗", "//"),
+                DownLeft => arrow("↙↙", "//"),
+                DownRight => arrow("↘↘", "\\\\"),
+            }
+            .to_owned(),
+            Terrain::Hole => "()".to_owned(),
+            Terrain::Teleporter(pair) => format!("%{pair}"),
+            Terrain::Key(id) => format!("k{}", char::from(b'a' + id)),
+            Terrain::Door(id) => format!("#{}", char::from(b'A' + id)),
+            Terrain::Plate => "__".to_owned(),
+            Terrain::Gate { open: false } => arrow("▒▒", "||").to_owned(),
+            Terrain::Gate { open: true } => arrow("░░", "::").to_owned(),
+        };
+        let colour = match self {
+            Terrain::Ice => Color::Cyan,
+            Terrain::Teleporter(_) => Color::Magenta,
+            Terrain::Key(_) | Terrain::Door(_) => Color::Yellow,
+            _ => Color::Reset,
+        };
+        Span::styled(text, Style::default().fg(colour))
+    }
+}
+
+impl Occupant {
+    /// How this looks standing on a target or not. Empty squares are drawn
+    /// as their terrain instead.
+    pub fn glyph(self, on_target: bool, glyphs: GlyphSet) -> &'static str {
+        use Occupant::*;
+        match glyphs {
+            GlyphSet::Blocks => match self {
+                Player { .. } if on_target => "@<",
+                Player { .. } => "@@",
+                Box if on_target => "░░",
+                Box => "[]",
+                Empty => "  ",
+                Turret { direction, .. } => match direction {
+                    Direction::Up => "▟▙",
+                    Direction::Left => "┫█",
+                    Direction::Right => "█┣",
+                    Direction::Down => "▜▛",
+                    Direction::UpLeft => "▛█",
+                    Direction::UpRight => "█▜",
+                    Direction::DownLeft => "▙█",
+                    Direction::DownRight => "█▟",
+                },
+                Bullet { .. } => "🞀🞂",
+            },
+            // Close to the usual XSB level characters, doubled up.
+            GlyphSet::Ascii => match self {
+                Player { .. } if on_target => "++",
+                Player { .. } => "@@",
+                Box if on_target => "**",
+                Box => "$$",
+                Empty => "  ",
+                Turret { direction, .. } => match direction {
+                    Direction::Up => "T^",
+                    Direction::Left => "<T",
+                    Direction::Right => "T>",
+                    Direction::Down => "Tv",
+                    Direction::UpLeft => "\\T",
+                    Direction::UpRight => "T/",
+                    Direction::DownLeft => "/T",
+                    Direction::DownRight => "T\\",
+                },
+                Bullet { .. } => "oo",
             },
-            Bullet {
-                direction,
-                on_target,
-            } => "🞀🞂",
         }
     }
+}
 
-    fn to_span(&self) -> Span {
-        self.to_string().into()
+impl KeyBind {
+    fn description(self) -> &'static str {
+        match self {
+            KeyBind::Quit => "Back / quit",
+            KeyBind::Up => "Move up",
+            KeyBind::Down => "Move down",
+            KeyBind::Left => "Move left",
+            KeyBind::Right => "Move right",
+            KeyBind::UpLeft => "Move up-left (hex)",
+            KeyBind::UpRight => "Move up-right (hex)",
+            KeyBind::DownLeft => "Move down-left (hex)",
+            KeyBind::DownRight => "Move down-right (hex)",
+            KeyBind::Pull => "Toggle pulling (reverse mode)",
+            KeyBind::Undo => "Undo this player's last move",
+            KeyBind::Switch => "Switch player",
+            KeyBind::PickBox => "Pick a box to steer, or next",
+            KeyBind::Cursor => "Pick a square to walk to",
+            KeyBind::Select => "Select",
+            KeyBind::Pause => "Pause / resume",
+            KeyBind::Help => "Toggle this help",
+            KeyBind::Share => "Share this level as text",
+            KeyBind::None => "",
+        }
     }
 }
 
-impl fmt::Debug for Cell {
+impl fmt::Debug for Occupant {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self.glyph(false, GlyphSet::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_snapshot;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::*;
+    use crate::records::Records;
+
+    /// An app that draws the same frame every time: nothing animates and no
+    /// saved records leak in.
+    fn app() -> App {
+        let mut app = App {
+            records: Records::default(),
+            ..App::default()
+        };
+        app.config.animations = false;
+        app
+    }
+
+    fn render(app: &App, width: u16, height: u16) -> Terminal<TestBackend> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        terminal
+    }
+
+    #[test]
+    fn menu() {
+        assert_snapshot!(render(&app(), 40, 12).backend());
+    }
+
+    #[test]
+    fn level_select() {
+        let mut app = app();
+        app.current_screen = CurrentScreen::LevelSelect(1);
+        assert_snapshot!(render(&app, 60, 10).backend());
+    }
+
+    #[test]
+    fn builtin_levels() {
+        let mut app = app();
+        for index in 0..app.levels.len() {
+            app.start_level(index);
+            let name = format!("builtin_level_{}", index + 1);
+            assert_snapshot!(name, render(&app, 60, 16).backend());
+        }
+    }
+
+    #[test]
+    fn level_complete() {
+        let mut app = app();
+        let summary = Summary {
+            level: 0,
+            attempt: Record {
+                moves: 8,
+                pushes: 1,
+                time: Duration::from_secs(75),
+            },
+            hp: 3,
+            best: None,
+            par_moves: Some(6),
+            par_pushes: Some(1),
+            solution: String::new(),
+            show_solution: false,
+        };
+        app.current_screen = CurrentScreen::Complete(summary, CompleteItem::Next);
+        assert_snapshot!(render(&app, 60, 16).backend());
+    }
+
+    #[test]
+    fn ending() {
+        let mut app = app();
+        app.current_screen = CurrentScreen::Ending;
+        assert_snapshot!(render(&app, 40, 10).backend());
+    }
+
+    #[test]
+    fn game_over() {
+        let mut app = app();
+        app.start_level(0);
+        let CurrentScreen::Game(level) = &mut app.current_screen else {
+            panic!("the level didn't start");
+        };
+        // Step onto the target, leaving room for a turret to fire into.
+        level.move_player(Direction::Right);
+        level.occupants[Coord(2, 3)] = Occupant::Turret {
+            direction: Direction::Right,
+            cooldown: 0,
+        };
+        for _ in 0..100 {
+            level.tick();
+        }
+        assert!(!level.players[0].alive);
+        app.update();
+        assert_snapshot!(render(&app, 50, 10).backend());
+    }
+
+    #[test]
+    fn glyph_sets() {
+        let mut app = app();
+        app.start_level(1);
+        // The buffer's debug output keeps the colours, which plain text loses.
+        for glyphs in [GlyphSet::Blocks, GlyphSet::Ascii] {
+            app.config.glyphs = glyphs;
+            let name = format!("glyphs_{glyphs:?}").to_lowercase();
+            assert_snapshot!(
+                name,
+                format!("{:?}", render(&app, 40, 14).backend().buffer())
+            );
+        }
+    }
+
+    #[test]
+    fn help() {
+        let mut app = app();
+        app.start_level(0);
+        app.show_help = true;
+        assert_snapshot!(render(&app, 60, 44).backend());
     }
 }
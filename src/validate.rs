@@ -0,0 +1,341 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use color_eyre::Result;
+
+use crate::app::Level;
+use crate::enums::{Direction, Occupant, Terrain};
+use crate::grid::{Coord, Grid, Offset};
+use crate::levels;
+use crate::topology::Topology;
+
+/// Something wrong with a level that would make it unplayable or unfair.
+pub enum Problem {
+    /// Nobody to move. Several players are fine, for Multiban levels.
+    NoPlayer,
+    Unbalanced {
+        boxes: usize,
+        targets: usize,
+    },
+    /// The player can walk off the board from here.
+    Leak(Coord),
+    UnreachableBox(Coord),
+    /// A box off target wedged into a corner, where it can never move again.
+    DeadBox(Coord),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Loaded levels have a one square margin, so these count from 1 in
+        // the file.
+        match self {
+            Problem::NoPlayer => write!(f, "has no player"),
+            Problem::Unbalanced { boxes, targets } => {
+                write!(f, "has {boxes} boxes but {targets} targets")
+            }
+            Problem::Leak(Coord(x, y)) => {
+                write!(
+                    f,
+                    "isn't walled in, there's a way out at row {y}, column {x}"
+                )
+            }
+            Problem::UnreachableBox(Coord(x, y)) => {
+                write!(f, "has a box the player can't reach at row {y}, column {x}")
+            }
+            Problem::DeadBox(Coord(x, y)) => {
+                write!(f, "has a box stuck in a corner at row {y}, column {x}")
+            }
+        }
+    }
+}
+
+/// Checks a board for the mistakes hand-made levels tend to have. Works on
+/// the bare grids, so a board with no player at all can still be checked.
+pub fn validate(terrain: &Grid<Terrain>, occupants: &Grid<Occupant>) -> Vec<Problem> {
+    let mut problems = vec![];
+    let players: Vec<Coord> = occupants
+        .iter()
+        .filter(|(_, occupant)| matches!(occupant, Occupant::Player { .. }))
+        .map(|(at, _)| at)
+        .collect();
+    if players.is_empty() {
+        problems.push(Problem::NoPlayer);
+    }
+
+    let boxes = occupants.count(Occupant::Box);
+    let targets = terrain.count(Terrain::Target);
+    // Each hole can swallow a box that then needs no target.
+    let holes = terrain.count(Terrain::Hole);
+    if targets > boxes || boxes - targets > holes {
+        problems.push(Problem::Unbalanced { boxes, targets });
+    }
+
+    // Without a player there is nowhere to flood from.
+    if !players.is_empty() {
+        let reach = reachable(terrain, occupants, &players);
+        let last = Coord(terrain.width() - 1, terrain.height() - 1);
+        let on_edge = |at: Coord| at.0 == 0 || at.1 == 0 || at.0 == last.0 || at.1 == last.1;
+        let reached: Vec<Coord> = reach
+            .iter()
+            .filter(|(_, &reached)| reached)
+            .map(|(at, _)| at)
+            .collect();
+        // Point at the gap itself rather than the margin beyond it.
+        let gap = reached.iter().find(|&&at| {
+            !on_edge(at)
+                && terrain
+                    .neighbours(at)
+                    .any(|(_, next)| on_edge(next) && reach[next])
+        });
+        if let Some(&at) = gap.or(reached.iter().find(|&&at| on_edge(at))) {
+            problems.push(Problem::Leak(at));
+        }
+        for (at, occupant) in occupants.iter() {
+            if *occupant == Occupant::Box && !reach[at] {
+                problems.push(Problem::UnreachableBox(at));
+            }
+        }
+    }
+
+    if terrain.topology() == Topology::Square {
+        let blocked = |at: Option<Coord>| at.is_none_or(|at| is_solid(terrain, occupants, at));
+        for (at, occupant) in occupants.iter() {
+            if *occupant != Occupant::Box || terrain[at] == Terrain::Target {
+                continue;
+            }
+            let wall = |direction| blocked(occupants.neighbour(at, direction));
+            let vertical = wall(Direction::Up) || wall(Direction::Down);
+            let horizontal = wall(Direction::Left) || wall(Direction::Right);
+            if vertical && horizontal {
+                problems.push(Problem::DeadBox(at));
+            }
+        }
+    }
+    problems
+}
+
+/// Tidies a level up: floor outside the walls is dropped, floor the players
+/// can never get to is walled in, and the board is turned and flipped into
+/// one standard orientation, so copies of a level that only differ in those
+/// ways come out the same.
+pub fn normalise(level: &Level) -> Result<Level> {
+    let (terrain, occupants) = (&level.terrain, &level.occupants);
+    let topology = terrain.topology();
+    let players: Vec<Coord> = level.players.iter().map(|avatar| avatar.location).collect();
+    let reach = reachable(terrain, occupants, &players);
+
+    // Walls that border the reachable floor stay. Everything else the
+    // players can't reach is walled in, unless it joins up with the edge of
+    // the board, in which case it's outside and is dropped.
+    // Walls touching the floor only at a corner stay too, except on hex
+    // boards, where the squares around a cell are mostly not cells at all.
+    let borders = |at: Coord| match topology {
+        Topology::Hex => terrain.neighbours(at).any(|(_, next)| reach[next]),
+        _ => (-1..=1).any(|dy| {
+            (-1..=1).any(|dx| {
+                at.checked_add(Offset(dx, dy))
+                    .and_then(|next| reach.get(next))
+                    .is_some_and(|&reached| reached)
+            })
+        }),
+    };
+    let keep = Grid::from_fn(terrain.width(), terrain.height(), |at| {
+        reach[at] || (is_solid(terrain, occupants, at) && borders(at))
+    });
+    let mut outside = Grid::from_fn(terrain.width(), terrain.height(), |_| false);
+    let last = Coord(terrain.width() - 1, terrain.height() - 1);
+    let mut queue: VecDeque<Coord> = keep
+        .iter()
+        .filter(|&(at, &kept)| {
+            !kept && (at.0 == 0 || at.1 == 0 || at.0 == last.0 || at.1 == last.1)
+        })
+        .map(|(at, _)| at)
+        .collect();
+    for &at in &queue {
+        outside[at] = true;
+    }
+    while let Some(at) = queue.pop_front() {
+        for direction in [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ] {
+            let Some(next) = at.checked_add(direction.into()) else {
+                continue;
+            };
+            if keep.get(next) == Some(&false) && !outside[next] {
+                outside[next] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    // Crop to what's left. Hex and triangle cells depend on whether `x + y`
+    // is even, so those boards can only be cropped by an even amount.
+    let used: Vec<Coord> = outside
+        .iter()
+        .filter(|(_, &out)| !out)
+        .map(|(at, _)| at)
+        .collect();
+    let mut left = used.iter().map(|at| at.0).min().unwrap_or(0);
+    let mut top = used.iter().map(|at| at.1).min().unwrap_or(0);
+    let right = used.iter().map(|at| at.0).max().unwrap_or(0);
+    let bottom = used.iter().map(|at| at.1).max().unwrap_or(0);
+    if topology != Topology::Square && !(left + top).is_multiple_of(2) {
+        if left > 0 {
+            left -= 1;
+        } else {
+            top -= 1;
+        }
+    }
+    let (width, height) = (right - left + 1, bottom - top + 1);
+    let source = |Coord(x, y)| Coord(x + left, y + top);
+    let terrain = Grid::from_fn(width, height, |at| {
+        let from = source(at);
+        match (outside[from], keep[from]) {
+            (false, true) => terrain[from],
+            // The unused columns of hex boards stay empty.
+            (false, false) if topology.is_cell(from) => Terrain::Wall,
+            _ => Terrain::Plain,
+        }
+    })
+    .with_topology(topology);
+    let occupants = Grid::from_fn(width, height, |at| {
+        let from = source(at);
+        if keep[from] {
+            occupants[from]
+        } else {
+            Occupant::Empty
+        }
+    })
+    .with_topology(topology);
+
+    // Other boards only have the identity, the first of the eight.
+    let count = if topology == Topology::Square { 8 } else { 1 };
+    let mut best: Option<(String, Level)> = None;
+    for symmetry in (0..count).map(Symmetry::new) {
+        let mut candidate: Level =
+            (symmetry.apply(&terrain), symmetry.apply(&occupants)).try_into()?;
+        candidate.meta = level.meta.clone();
+        candidate.index = level.index;
+        let text = levels::to_xsb(&[candidate.clone()]);
+        if best.as_ref().is_none_or(|(best, _)| text < *best) {
+            best = Some((text, candidate));
+        }
+    }
+    Ok(best
+        .map(|(_, level)| level)
+        .expect("there is always the identity"))
+}
+
+/// Squares that nothing can ever stand on.
+fn is_solid(terrain: &Grid<Terrain>, occupants: &Grid<Occupant>, at: Coord) -> bool {
+    terrain[at] == Terrain::Wall || matches!(occupants[at], Occupant::Turret { .. })
+}
+
+/// Every square a player could walk to if the boxes were out of the way.
+fn reachable(terrain: &Grid<Terrain>, occupants: &Grid<Occupant>, from: &[Coord]) -> Grid<bool> {
+    let mut seen = Grid::from_fn(terrain.width(), terrain.height(), |_| false);
+    let mut queue = VecDeque::from(from.to_vec());
+    for &at in from {
+        seen[at] = true;
+    }
+    while let Some(at) = queue.pop_front() {
+        for (_, next) in terrain.neighbours(at) {
+            if !seen[next] && !is_solid(terrain, occupants, next) {
+                seen[next] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+    seen
+}
+
+/// One of the eight ways to turn or flip a square board: swap the axes, then
+/// mirror either of them.
+struct Symmetry {
+    transpose: bool,
+    flip_x: bool,
+    flip_y: bool,
+}
+
+impl Symmetry {
+    /// The `i`th of the eight, counting from the identity.
+    fn new(i: u8) -> Self {
+        Self {
+            transpose: i & 4 != 0,
+            flip_x: i & 2 != 0,
+            flip_y: i & 1 != 0,
+        }
+    }
+
+    fn apply<T: Turn>(&self, grid: &Grid<T>) -> Grid<T> {
+        let (width, height) = match self.transpose {
+            true => (grid.height(), grid.width()),
+            false => (grid.width(), grid.height()),
+        };
+        Grid::from_fn(width, height, |Coord(x, y)| {
+            let x = if self.flip_x { width - 1 - x } else { x };
+            let y = if self.flip_y { height - 1 - y } else { y };
+            let from = if self.transpose {
+                Coord(y, x)
+            } else {
+                Coord(x, y)
+            };
+            grid[from].turn(|direction| self.direction(direction))
+        })
+        .with_topology(grid.topology())
+    }
+
+    fn direction(&self, direction: Direction) -> Direction {
+        let Offset(mut dx, mut dy) = direction.into();
+        if self.transpose {
+            (dx, dy) = (dy, dx);
+        }
+        if self.flip_x {
+            dx = -dx;
+        }
+        if self.flip_y {
+            dy = -dy;
+        }
+        match (dx, dy) {
+            (0, -1) => Direction::Up,
+            (0, 1) => Direction::Down,
+            (-1, 0) => Direction::Left,
+            _ => Direction::Right,
+        }
+    }
+}
+
+/// Squares that point somewhere, and so turn along with the board.
+trait Turn: Copy {
+    fn turn(self, f: impl Fn(Direction) -> Direction) -> Self;
+}
+
+impl Turn for Terrain {
+    fn turn(self, f: impl Fn(Direction) -> Direction) -> Self {
+        match self {
+            Terrain::OneWay(direction) => Terrain::OneWay(f(direction)),
+            other => other,
+        }
+    }
+}
+
+impl Turn for Occupant {
+    fn turn(self, f: impl Fn(Direction) -> Direction) -> Self {
+        match self {
+            Occupant::Turret {
+                direction,
+                cooldown,
+            } => Occupant::Turret {
+                direction: f(direction),
+                cooldown,
+            },
+            Occupant::Bullet { direction } => Occupant::Bullet {
+                direction: f(direction),
+            },
+            other => other,
+        }
+    }
+}